@@ -0,0 +1,95 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use rocketchat_message::{RocketChat, RocketChatAttachment, RocketChatMessage};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Allocator counting allocations, to report them per body
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const BODIES: usize = 1000;
+
+/// A digest message of about 2 KB, with a few attachments
+fn digest_message() -> RocketChatMessage {
+    RocketChatMessage::new()
+        .set_text("Nightly digest")
+        .set_attachments(
+            (0..8)
+                .map(|i| {
+                    RocketChatAttachment::new()
+                        .set_title(format!("Job {}", i))
+                        .set_text("Finished in 12 minutes, 3 warnings, no errors")
+                        .set_color("#2eb886")
+                        .set_fields(vec![
+                            ("Duration", "12m", true).into(),
+                            ("Warnings", "3", true).into(),
+                        ])
+                })
+                .collect(),
+        )
+}
+
+/// Prints the allocations of building the bodies of messages
+fn report_allocations(name: &str, client: &RocketChat, msg: &RocketChatMessage) {
+    // Warms up the buffers kept between bodies
+    client.webhook_body(msg).unwrap();
+
+    let (allocations, bytes) = (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    );
+    for _ in 0..BODIES {
+        black_box(client.webhook_body(msg).unwrap());
+    }
+    println!(
+        "{}: {:.1} allocations, {} bytes allocated per body",
+        name,
+        (ALLOCATIONS.load(Ordering::Relaxed) - allocations) as f64 / BODIES as f64,
+        (ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes) / BODIES,
+    );
+}
+
+fn body_encoding(c: &mut Criterion) {
+    let client = RocketChat::new("http://localhost/hooks/TOKEN", "#channel");
+    let heartbeat = RocketChatMessage::new().set_text("Heartbeat: service is up");
+    let digest = digest_message();
+
+    report_allocations("heartbeat", &client, &heartbeat);
+    report_allocations("digest", &client, &digest);
+
+    let mut group = c.benchmark_group("body_encoding");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("heartbeat", |b| {
+        b.iter(|| black_box(client.webhook_body(&heartbeat).unwrap()))
+    });
+    group.bench_function("digest", |b| {
+        b.iter(|| black_box(client.webhook_body(&digest).unwrap()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, body_encoding);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use rocketchat_message::{Field, RocketChatAttachment};
+
+const FIELDS: usize = 10_000;
+
+fn field(i: usize) -> Field {
+    Field::new()
+        .set_title(format!("Field {}", i))
+        .set_value("Value")
+        .set_short(true)
+}
+
+fn fields_builder(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fields");
+    group.throughput(Throughput::Elements(FIELDS as u64));
+    group.bench_function("set_fields", |b| {
+        b.iter(|| {
+            let fields: Vec<Field> = (0..FIELDS).map(field).collect();
+            black_box(RocketChatAttachment::new().set_fields(fields))
+        })
+    });
+    group.bench_function("fields_builder", |b| {
+        b.iter(|| {
+            let mut builder = RocketChatAttachment::new().fields_builder().reserve(FIELDS);
+            for i in 0..FIELDS {
+                builder.push(field(i));
+            }
+            black_box(builder.finish())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, fields_builder);
+criterion_main!(benches);
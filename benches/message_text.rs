@@ -0,0 +1,30 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use rocketchat_message::RocketChatMessage;
+
+const TEXT: &str = "Heartbeat: service is up";
+
+/// Compares the text of a heartbeat allocated by `set_text` with a text
+/// allocated beforehand, which costs what a borrowed static text would
+fn message_text(c: &mut Criterion) {
+    let mut group = c.benchmark_group("heartbeat");
+    group.bench_function("set_text", |b| {
+        b.iter(|| {
+            let msg = RocketChatMessage::new().set_text(black_box(TEXT));
+            black_box(serde_json::to_vec(&msg).unwrap())
+        })
+    });
+    group.bench_function("set_text without allocation", |b| {
+        b.iter_batched(
+            || TEXT.to_string(),
+            |text| {
+                let msg = RocketChatMessage::new().set_text(text);
+                black_box(serde_json::to_vec(&msg).unwrap())
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, message_text);
+criterion_main!(benches);
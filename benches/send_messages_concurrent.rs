@@ -0,0 +1,56 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rocketchat_message::{RocketChat, RocketChatAttachment, RocketChatMessage};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::{response, MockServer};
+
+const MESSAGES: usize = 1000;
+const LIMIT: usize = 32;
+
+fn messages() -> Vec<RocketChatMessage> {
+    (0..MESSAGES)
+        .map(|i| {
+            RocketChatMessage::new()
+                .set_text(format!("Message {}", i))
+                .set_attachments(vec![RocketChatAttachment::new()
+                    .set_title("Attachment title")
+                    .set_text("Attachment text")
+                    .set_color("#c97149")])
+        })
+        .collect()
+}
+
+fn send_messages_concurrent(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    // Local webhook answering 200 to every request, with keep-alive
+    let server = MockServer::start(|_| response("200 OK", &[], b"{\"success\":true}"));
+    let client = RocketChat::new(
+        format!("{}/hooks/TOKEN", server.url),
+        "#channel".to_string(),
+    );
+
+    let mut group = c.benchmark_group("send_messages_concurrent");
+    group.throughput(Throughput::Elements(MESSAGES as u64));
+    group.sample_size(20);
+    group.bench_function("1000 messages", |b| {
+        b.iter_batched(
+            || {
+                server.clear_requests();
+                messages()
+            },
+            |msgs| {
+                runtime
+                    .block_on(client.send_messages_concurrent(msgs, LIMIT))
+                    .into_result()
+                    .unwrap()
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, send_messages_concurrent);
+criterion_main!(benches);
@@ -0,0 +1,143 @@
+//! Emoji shortcodes for messages
+//!
+//! ```
+//! let message = RocketChatMessage::new().set_emoji(emoji::ROCKET);
+//! ```
+
+use anyhow::*;
+use std::borrow::Cow;
+use std::fmt;
+
+/// A structure representing an emoji shortcode, like `:rocket:`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Emoji(Cow<'static, str>);
+
+impl Emoji {
+    /// Creates a new emoji, checking the shortcode is wrapped in colons
+    ///
+    /// ```
+    /// let emoji = Emoji::new(":rocket:")?;
+    /// ```
+    pub fn new<S: Into<String>>(shortcode: S) -> Result<Self, Error> {
+        let shortcode = shortcode.into();
+
+        let name = shortcode
+            .strip_prefix(':')
+            .and_then(|name| name.strip_suffix(':'))
+            .ok_or_else(|| anyhow!("Invalid emoji: {} is not wrapped in colons", shortcode))?;
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '_' | '+' | '-'))
+        {
+            bail!("Invalid emoji: {}", shortcode);
+        }
+        Ok(Self(Cow::Owned(shortcode)))
+    }
+
+    /// Returns the shortcode of the emoji
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Emoji {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Emoji> for String {
+    fn from(emoji: Emoji) -> Self {
+        emoji.0.into_owned()
+    }
+}
+
+/// Common reactions to messages, see `RocketChat::react`
+///
+/// ```
+/// client.react(&sent.id, Reaction::Eyes).await?;
+/// client.react(&sent.id, Reaction::Custom(Emoji::new(":custom_emoji:")?)).await?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Reaction {
+    /// :+1:
+    ThumbsUp,
+    /// :-1:
+    ThumbsDown,
+    /// :white_check_mark:
+    Check,
+    /// :x:
+    Cross,
+    /// :eyes:
+    Eyes,
+    /// :fire:
+    Fire,
+    /// :tada:
+    Tada,
+    /// :warning:
+    Warning,
+    /// Any other emoji
+    Custom(Emoji),
+}
+
+impl Reaction {
+    /// Returns the shortcode of the reaction
+    pub fn as_str(&self) -> &str {
+        match self {
+            Reaction::ThumbsUp => ":+1:",
+            Reaction::ThumbsDown => ":-1:",
+            Reaction::Check => WHITE_CHECK_MARK.as_str(),
+            Reaction::Cross => X.as_str(),
+            Reaction::Eyes => EYES.as_str(),
+            Reaction::Fire => FIRE.as_str(),
+            Reaction::Tada => TADA.as_str(),
+            Reaction::Warning => WARNING.as_str(),
+            Reaction::Custom(emoji) => emoji.as_str(),
+        }
+    }
+}
+
+impl fmt::Display for Reaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<Emoji> for Reaction {
+    fn from(emoji: Emoji) -> Self {
+        Reaction::Custom(emoji)
+    }
+}
+
+impl From<Reaction> for String {
+    fn from(reaction: Reaction) -> Self {
+        match reaction {
+            Reaction::Custom(emoji) => emoji.into(),
+            reaction => reaction.as_str().to_string(),
+        }
+    }
+}
+
+/// :rocket:
+pub const ROCKET: Emoji = Emoji(Cow::Borrowed(":rocket:"));
+/// :fire:
+pub const FIRE: Emoji = Emoji(Cow::Borrowed(":fire:"));
+/// :warning:
+pub const WARNING: Emoji = Emoji(Cow::Borrowed(":warning:"));
+/// :rotating_light:
+pub const ROTATING_LIGHT: Emoji = Emoji(Cow::Borrowed(":rotating_light:"));
+/// :white_check_mark:
+pub const WHITE_CHECK_MARK: Emoji = Emoji(Cow::Borrowed(":white_check_mark:"));
+/// :x:
+pub const X: Emoji = Emoji(Cow::Borrowed(":x:"));
+/// :information_source:
+pub const INFORMATION_SOURCE: Emoji = Emoji(Cow::Borrowed(":information_source:"));
+/// :bell:
+pub const BELL: Emoji = Emoji(Cow::Borrowed(":bell:"));
+/// :eyes:
+pub const EYES: Emoji = Emoji(Cow::Borrowed(":eyes:"));
+/// :tada:
+pub const TADA: Emoji = Emoji(Cow::Borrowed(":tada:"));
+/// :robot:
+pub const ROBOT: Emoji = Emoji(Cow::Borrowed(":robot:"));
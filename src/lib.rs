@@ -36,20 +36,229 @@
 //!     RocketChatMessage::new().set_text("Message2"),
 //! ];
 //!
-//! client.send_messages(msgs).await?;
+//! client.send_messages(msgs).await.into_result()?;
 //! ```
 
+pub mod emoji;
+mod metrics;
+mod pool;
+mod template;
+mod thread;
+mod worker;
+
+pub use emoji::{Emoji, Reaction};
+pub use metrics::Metrics;
+pub use pool::{PoolStrategy, RocketChatPool};
+pub use template::MessageTemplate;
+pub use thread::{MemoryThreadStore, ThreadHandle, ThreadStore};
+pub use worker::SyncSender;
+
 use anyhow::*;
+use bytes::Bytes;
+use futures_util::stream::{self, StreamExt};
 use reqwest::blocking::Response;
-use serde::Serialize;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::redirect::Policy;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, Read};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A structure representing a validated rocket chat webhook url
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookUrl(String);
+
+impl WebhookUrl {
+    /// Creates a new webhook url, checking it is an absolute http(s) url
+    ///
+    /// ```
+    /// let url = WebhookUrl::new("https://chat.example.com/hooks/TOKEN")?;
+    /// ```
+    pub fn new<S: Into<String>>(url: S) -> Result<Self, Error> {
+        let url = url.into();
+        let parsed =
+            reqwest::Url::parse(&url).map_err(|e| anyhow!("Invalid webhook url: {}", e))?;
+
+        if !matches!(parsed.scheme(), "http" | "https") || !parsed.has_host() {
+            bail!("Invalid webhook url: expected an http(s) url");
+        }
+        Ok(Self(url))
+    }
+
+    /// Returns the webhook url as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for WebhookUrl {
+    type Err = Error;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        Self::new(url)
+    }
+}
+
+impl TryFrom<&str> for WebhookUrl {
+    type Error = Error;
+
+    fn try_from(url: &str) -> Result<Self, Self::Error> {
+        Self::new(url)
+    }
+}
+
+impl TryFrom<String> for WebhookUrl {
+    type Error = Error;
+
+    fn try_from(url: String) -> Result<Self, Self::Error> {
+        Self::new(url)
+    }
+}
+
+impl AsRef<str> for WebhookUrl {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for WebhookUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A trait for types which can be sent as a rocket chat message
+///
+/// ```
+/// struct Alert {
+///     name: String,
+/// }
+///
+/// impl ToRocketChatMessage for Alert {
+///     fn to_message(&self) -> RocketChatMessage {
+///         RocketChatMessage::new().set_text(format!("Alert: {}", self.name))
+///     }
+/// }
+/// ```
+pub trait ToRocketChatMessage {
+    /// Builds the rocket chat message
+    fn to_message(&self) -> RocketChatMessage;
+}
+
+/// A trait for types which can be added as a rocket chat attachment
+///
+/// ```
+/// struct DeployEvent {
+///     version: String,
+/// }
+///
+/// impl ToRocketChatAttachment for DeployEvent {
+///     fn to_attachment(&self) -> RocketChatAttachment {
+///         RocketChatAttachment::new().set_title(format!("Deployed {}", self.version))
+///     }
+/// }
+/// ```
+pub trait ToRocketChatAttachment {
+    /// Builds the rocket chat attachment
+    fn to_attachment(&self) -> RocketChatAttachment;
+}
+
+impl ToRocketChatMessage for RocketChatMessage {
+    fn to_message(&self) -> RocketChatMessage {
+        self.clone()
+    }
+}
+
+impl ToRocketChatMessage for str {
+    fn to_message(&self) -> RocketChatMessage {
+        RocketChatMessage::new().set_text(self)
+    }
+}
+
+impl ToRocketChatAttachment for RocketChatAttachment {
+    fn to_attachment(&self) -> RocketChatAttachment {
+        self.clone()
+    }
+}
 
 /// A structure representing a rocket chat client
-#[derive(Debug)]
+///
+/// Cloning a client is cheap: the HTTP client, with its connection pool, and
+/// the configuration are shared between clones behind `Arc`s, so a client can
+/// be cloned for each thread or task instead of being wrapped.
+///
+/// The `Debug` output masks the webhook url, only its host is shown.
+#[derive(Clone)]
 pub struct RocketChat {
     /// Webhook url from rocket chat
-    webhook_url: String,
-    /// Channel used to send messages (@user or #channel)
-    channel: String,
+    webhook_url: Arc<str>,
+    /// Base url of the rocket chat server, for permalinks (default origin of the webhook url)
+    server_url: Option<Arc<str>>,
+    /// Channel used to send messages (@user or #channel), integration default if none
+    channel: Option<Arc<str>>,
+    /// Room id used to send messages instead of channel
+    room_id: Option<Arc<str>>,
+    /// Number of retries after a failed send (default 0)
+    max_retries: u32,
+    /// Delay before the first retry, doubled for each next one (default `RETRY_BACKOFF`)
+    retry_backoff: Duration,
+    /// Number of retries shared by all messages of a batch
+    retry_budget: Option<u32>,
+    /// Pretty-print the JSON body (default false)
+    pretty_json: bool,
+    /// Maximum number of attachments sent per message (default none)
+    max_attachments: Option<usize>,
+    /// Attachment appended to every message
+    default_attachment: Option<Arc<RocketChatAttachment>>,
+    /// Format of the timestamp appended to texts (default none)
+    append_timestamp: Option<Arc<str>>,
+    /// Key wrapping the message in the body, like `{"payload": {...}}` (default none)
+    payload_key: Option<Arc<str>>,
+    /// Recently sent messages, shared between clones
+    dedup: Option<Arc<DedupWindow>>,
+    /// Colors of attachment severities, overriding the default colors
+    severity_colors: Arc<HashMap<Severity, String>>,
+    /// Reject bodies larger than the payload limit before sending (default false)
+    enforce_size_limit: bool,
+    /// Maximum size of a body in bytes (default `MAX_PAYLOAD_BYTES`)
+    max_payload_bytes: usize,
+    /// Maximum size in bytes of a response body read for an error (default `MAX_ERROR_BODY_BYTES`)
+    max_error_body_bytes: usize,
+    /// Content type of the body (default application/json)
+    content_type: Arc<str>,
+    /// Header of correlation ids (default X-Correlation-Id)
+    correlation_header: Arc<str>,
+    /// Separator between texts of combined messages (default newline)
+    join_separator: Arc<str>,
+    /// Query parameters appended to the webhook url
+    url_tokens: Arc<Vec<(String, String)>>,
+    /// Timeout of a whole request
+    timeout: Option<Duration>,
+    /// Timeout of the connection to the server
+    connect_timeout: Option<Duration>,
+    /// Redirect policy of HTTP clients (default of reqwest)
+    redirect_policy: Option<Arc<Policy>>,
+    /// Use the proxy of environment variables (default true)
+    system_proxy: bool,
+    /// HTTP client shared between clones
+    client: Arc<reqwest::Client>,
+    /// Whether the HTTP client was set with `set_http_client`, so it is never rebuilt
+    custom_client: bool,
+    /// Authentication for REST API calls
+    auth: Option<Arc<RestAuth>>,
+    /// HTTP basic authentication of webhook requests, user and password
+    basic_auth: Option<Arc<(String, Option<String>)>>,
+    /// Metrics recorded on each send
+    metrics: Option<Arc<dyn Metrics>>,
+    /// Threads opened with `reply_to_key`, shared by clones
+    thread_store: Arc<dyn ThreadStore>,
 }
 
 impl RocketChat {
@@ -59,13 +268,80 @@ impl RocketChat {
     /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
     /// ```
     pub fn new<S: Into<String>>(webhook_url: S, channel: S) -> Self {
+        Self::new_default_channel(webhook_url).set_channel(channel)
+    }
+
+    /// Creates a new rocket chat client from a validated webhook url
+    ///
+    /// Unlike `new`, the webhook url is checked, so swapping the url and the
+    /// channel is reported as an error.
+    ///
+    /// ```
+    /// let client = RocketChat::try_new("https://chat.example.com/hooks/TOKEN", "#channel")?;
+    /// ```
+    pub fn try_new<U, S>(webhook_url: U, channel: S) -> Result<Self, Error>
+    where
+        U: TryInto<WebhookUrl>,
+        U::Error: Into<Error>,
+        S: Into<String>,
+    {
+        let webhook_url = webhook_url.try_into().map_err(Into::into)?;
+
+        Ok(Self::new(webhook_url.0, channel.into()))
+    }
+
+    /// Creates a new rocket chat client without channel, messages are posted
+    /// on the channel configured in the webhook integration
+    ///
+    /// ```
+    /// let client = RocketChat::new_default_channel("ROCKET_CHAT_WEBHOOK_URL");
+    /// ```
+    pub fn new_default_channel<S: Into<String>>(webhook_url: S) -> Self {
         Self {
-            webhook_url: webhook_url.into(),
-            channel: channel.into(),
+            webhook_url: webhook_url.into().into(),
+            server_url: None,
+            channel: None,
+            room_id: None,
+            max_retries: 0,
+            retry_backoff: RETRY_BACKOFF,
+            retry_budget: None,
+            pretty_json: false,
+            max_attachments: None,
+            default_attachment: None,
+            append_timestamp: None,
+            payload_key: None,
+            dedup: None,
+            severity_colors: Arc::new(HashMap::new()),
+            enforce_size_limit: false,
+            max_payload_bytes: MAX_PAYLOAD_BYTES,
+            max_error_body_bytes: MAX_ERROR_BODY_BYTES,
+            content_type: "application/json".into(),
+            correlation_header: "X-Correlation-Id".into(),
+            join_separator: "\n".into(),
+            url_tokens: Arc::new(Vec::new()),
+            timeout: None,
+            connect_timeout: None,
+            redirect_policy: None,
+            system_proxy: true,
+            client: Arc::new(reqwest::Client::new()),
+            custom_client: false,
+            auth: None,
+            basic_auth: None,
+            metrics: None,
+            thread_store: Arc::new(MemoryThreadStore::default()),
         }
     }
 
-    /// Changes the channel to post messages
+    /// Creates a new rocket chat client without channel, same as `new_default_channel`
+    ///
+    /// ```
+    /// let client = RocketChat::with_default_channel("ROCKET_CHAT_WEBHOOK_URL");
+    /// ```
+    pub fn with_default_channel(webhook_url: impl Into<String>) -> Self {
+        Self::new_default_channel(webhook_url)
+    }
+
+    /// Changes the channel to post messages, replacing the room id if set
     ///
     /// ```
     /// let mut client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
@@ -73,335 +349,3544 @@ impl RocketChat {
     /// client = client.set_channel("#channel2");
     /// ```
     pub fn set_channel<S: Into<String>>(mut self, channel: S) -> Self {
-        self.channel = channel.into();
+        self.channel = Some(channel.into().into());
+        self.room_id = None;
         self
     }
 
-    /// Send simple text message
+    /// Changes the channel to post messages, normalized with `normalize_channel`
     ///
     /// ```
-    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    /// let client = RocketChat::new_default_channel("ROCKET_CHAT_WEBHOOK_URL").try_set_channel("general")?;
     ///
-    /// client.send_text("Text").await?;
+    /// assert_eq!(client.channel(), Some("#general"));
     /// ```
-    pub async fn send_text<S: Into<String>>(&self, msg: S) -> Result<reqwest::Response, Error> {
-        let msg = RocketChatMessage::new().set_text(msg.into());
-
-        self.send_message(msg).await
+    pub fn try_set_channel(self, channel: &str) -> Result<Self, Error> {
+        Ok(self.set_channel(normalize_channel(channel)?))
     }
 
-    /// Send simple text message (sync)
+    /// Changes the base url of the rocket chat server, used for permalinks
     ///
-    /// ```
-    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    /// By default, the server url of `set_auth` is used, or the origin of the
+    /// webhook url.
     ///
-    /// client.send_text_sync("Text");
     /// ```
-    pub fn send_text_sync<S: Into<String>>(&self, msg: S) -> Result<Response, Error> {
-        let msg = RocketChatMessage::new().set_text(msg.into());
-
-        self.send_message_sync(msg)
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_server_url("https://chat.example.com");
+    /// ```
+    pub fn set_server_url<S: Into<String>>(mut self, server_url: S) -> Self {
+        self.server_url = Some(server_url.into().into());
+        self
     }
 
-    /// Send a rocket chat message
+    /// Changes the room id to post messages, instead of the channel name
     ///
-    /// ```
-    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
-    /// let msg = RocketChatMessage::new().set_text("Text");
+    /// The room id and the channel are mutually exclusive, the room id replaces
+    /// the channel if set.
     ///
-    /// client.send_message(msg).await;
     /// ```
-    pub async fn send_message(&self, msg: RocketChatMessage) -> Result<reqwest::Response, Error> {
-        let client = reqwest::Client::new();
-
-        let msg = RocketChatMessagePayload::from((msg, self.channel.clone()));
-
-        let res = client
-            .post(&self.webhook_url)
-            .json(&msg)
-            .send()
-            .await
-            .map_err(|e| anyhow!("Request error: {:?}", e.status()))?;
-
-        if res.status() == 200 {
-            Ok(res)
-        } else {
-            Err(anyhow!("Response error: {}", res.status())) // Manage error if status is not 200
-        }
+    /// let client = RocketChat::new_default_channel("ROCKET_CHAT_WEBHOOK_URL").set_room_id("ROOM_ID");
+    /// ```
+    pub fn set_room_id<S: Into<String>>(mut self, room_id: S) -> Self {
+        self.room_id = Some(room_id.into().into());
+        self.channel = None;
+        self
     }
 
-    /// Send a rocket chat message (sync)
+    /// Returns a new client posting messages to another channel
+    ///
+    /// The HTTP client is shared with the original client.
     ///
     /// ```
     /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
-    /// let msg = RocketChatMessage::new().set_text("Text");
     ///
-    /// client.send_message_sync(msg);
+    /// let client2 = client.clone_with_channel("#channel2");
     /// ```
-    pub fn send_message_sync(&self, msg: RocketChatMessage) -> Result<Response, Error> {
-        let client = reqwest::blocking::Client::new();
-
-        let msg = RocketChatMessagePayload::from((msg, self.channel.clone()));
-
-        let res = client
-            .post(&self.webhook_url)
-            .json(&msg)
-            .send()
-            .map_err(|e| anyhow!("Request error: {:?}", e.status()))?;
-
-        if res.status() == 200 {
-            Ok(res)
-        } else {
-            Err(anyhow!("Response error: {}", res.status())) // Manage error if status is not 200
-        }
+    pub fn clone_with_channel<S: Into<String>>(&self, channel: S) -> Self {
+        self.clone().set_channel(channel)
     }
 
-    /// Send multiple messages at the same time on the same channel
+    /// Returns the channel to post messages, none for the integration default
     ///
     /// ```
-    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    /// assert_eq!(client.channel(), Some("#channel"));
+    /// ```
+    pub fn channel(&self) -> Option<&str> {
+        self.channel.as_deref()
+    }
+
+    /// Returns the webhook url
     ///
-    /// let msgs = vec![
-    ///    RocketChatMessage::new().set_text("Text"),
-    ///    RocketChatMessage::new().set_text("Text2"),
-    /// ];
+    /// The url contains the secret token of the integration, avoid logging it.
     ///
-    /// client.send_messages(msgs).await?;
     /// ```
-    pub async fn send_messages(&self, msgs: Vec<RocketChatMessage>) -> Result<(), Error> {
-        for msg in msgs {
-            self.send_message(msg).await?;
-        }
-        Ok(())
+    /// let webhook_url = client.webhook_url();
+    /// ```
+    pub fn webhook_url(&self) -> &str {
+        &self.webhook_url
     }
 
-    /// Send multiple messages at the same time on the same channel (sync)
+    /// Creates clients from a map of names to webhook urls and channels
     ///
     /// ```
-    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
-    ///
-    /// let msgs = vec![
-    ///    RocketChatMessage::new().set_text("Text"),
-    ///    RocketChatMessage::new().set_text("Text2"),
-    /// ];
+    /// let mut tenants = HashMap::new();
+    /// tenants.insert("acme".to_string(), ("ROCKET_CHAT_WEBHOOK_URL".to_string(), "#alerts".to_string()));
     ///
-    /// client.send_messages_sync(msgs);
+    /// let clients = RocketChat::from_map(tenants);
     /// ```
-    pub fn send_messages_sync(&self, msgs: Vec<RocketChatMessage>) -> Result<(), Error> {
-        for msg in msgs {
-            self.send_message_sync(msg)?;
-        }
-        Ok(())
+    pub fn from_map(map: HashMap<String, (String, String)>) -> HashMap<String, RocketChat> {
+        map.into_iter()
+            .map(|(name, (webhook_url, channel))| (name, RocketChat::new(webhook_url, channel)))
+            .collect()
     }
-}
-
-/// A structure representing a rocket chat field for attachments
-#[derive(Serialize, Default)]
-pub struct Field {
-    /// Size of field (default false by rocket chat)
-    pub short: Option<bool>,
-    /// Title of field
-    pub title: String,
-    /// Value of field
-    pub value: String,
-}
 
-impl Field {
-    /// Create new field
+    /// Creates clients from a map of names to webhook urls and channels,
+    /// sharing the configuration of this client (timeouts, retries, HTTP client)
     ///
     /// ```
-    /// let field = Field::new();
+    /// let template = RocketChat::new_default_channel("").set_max_retries(3);
+    ///
+    /// let clients = template.from_map_with(tenants);
     /// ```
-    pub fn new() -> Self {
-        Field::default()
+    pub fn from_map_with(
+        &self,
+        map: HashMap<String, (String, String)>,
+    ) -> HashMap<String, RocketChat> {
+        map.into_iter()
+            .map(|(name, (webhook_url, channel))| {
+                let mut client = self.clone_with_channel(channel);
+                client.webhook_url = webhook_url.into();
+                (name, client)
+            })
+            .collect()
     }
 
-    /// Change the title of the field
+    /// Changes the HTTP client used to send messages (async)
+    ///
+    /// The client is kept as is, whatever the order of the builder calls: the
+    /// options of `set_connect_timeout`, `set_redirect_policy` and
+    /// `use_system_proxy` only apply to sync sends, so configure them on the
+    /// given client for async sends.
     ///
     /// ```
-    /// let field = Field::new().set_title("Title");
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_http_client(reqwest::Client::new());
     /// ```
-    pub fn set_title<S: Into<String>>(mut self, title: S) -> Self {
-        self.title = title.into();
+    pub fn set_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = Arc::new(client);
+        self.custom_client = true;
         self
     }
 
-    /// Change the value of the field
+    /// Changes the authentication used for REST API calls
+    ///
+    /// Some features, like reactions, are not available with webhooks and use
+    /// the REST API of the server with a personal access token.
     ///
     /// ```
-    /// let field = Field::new().set_value("Value");
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_auth("https://chat.example.com", "USER_ID", "AUTH_TOKEN");
     /// ```
-    pub fn set_value<S: Into<String>>(mut self, value: S) -> Self {
-        self.value = value.into();
+    pub fn set_auth<S: Into<String>>(mut self, server_url: S, user_id: S, auth_token: S) -> Self {
+        self.auth = Some(Arc::new(RestAuth {
+            server_url: server_url.into(),
+            user_id: user_id.into(),
+            auth_token: auth_token.into(),
+        }));
         self
     }
 
-    /// Change the short of the field
+    /// Changes the HTTP basic authentication of webhook requests, like for a
+    /// reverse proxy protecting the webhook
+    ///
+    /// The credentials are not sent to the REST API (see `set_auth`).
     ///
     /// ```
-    /// let field = Field::new().set_short(true);
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_basic_auth("user", Some("password".to_string()));
     /// ```
-    pub fn set_short(mut self, value: bool) -> Self {
-        self.short = Some(value);
+    pub fn set_basic_auth(mut self, user: impl Into<String>, pass: Option<String>) -> Self {
+        self.basic_auth = Some(Arc::new((user.into(), pass)));
         self
     }
-}
-
-/// A structure representing a rocket chat attachment
-#[derive(Serialize, Default)]
-pub struct RocketChatAttachment {
-    /// Title of attachment
-    pub title: Option<String>,
-    /// Link for title of attachment
-    pub title_link: Option<String>,
-    /// Color on border left of attachment
-    pub color: Option<String>,
-    /// Author name of attachment
-    pub author_name: Option<String>,
-    /// Author icon of attachment (displayed only if author name is defined)
-    pub author_icon: Option<String>,
-    /// Text of attachment
-    pub text: Option<String>,
-    /// Image of attachment
-    pub image_url: Option<String>,
-    /// Fields of attachment
-    pub fields: Vec<Field>,
-}
 
-impl RocketChatAttachment {
-    /// Create new attachment
+    /// Changes the number of retries after a failed send
+    ///
+    /// A send is retried on server errors (5xx or 429) and on request errors,
+    /// after an exponential backoff (see `set_retry_backoff`). Timeouts are not
+    /// retried, except connect timeouts: a timed out request may have been
+    /// delivered, and retrying it could post the message twice.
     ///
     /// ```
-    /// let attachment = RocketChatAttachment::new();
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel").set_max_retries(3);
     /// ```
-    pub fn new() -> Self {
-        RocketChatAttachment::default()
+    pub fn set_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
     }
 
-    /// Change the title of the attachment
+    /// Changes the delay before the first retry (default `RETRY_BACKOFF`)
+    ///
+    /// The delay doubles for each next retry of a message, up to
+    /// `MAX_RETRY_DELAY`. A zero delay retries straight away.
     ///
     /// ```
-    /// let attachment = RocketChatAttachment::new().set_title("Title");
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_max_retries(3)
+    ///     .set_retry_backoff(Duration::from_secs(1));
     /// ```
-    pub fn set_title<S: Into<String>>(mut self, title: S) -> Self {
-        self.title = Some(title.into());
+    pub fn set_retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
         self
     }
 
-    /// Change the title link of attachment
+    /// Changes the content type header of webhook requests (default `application/json`)
+    ///
+    /// The body is still JSON. This is only meant for proxies expecting another
+    /// content type, like `text/plain`, as rocket chat itself may reject it.
+    /// REST API calls of `set_auth` always send `application/json`.
     ///
     /// ```
-    /// let attachment = RocketChatAttachment::new().set_title_link("https://google.fr");
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_content_type("text/plain");
     /// ```
-    pub fn set_title_link<S: Into<String>>(mut self, title_link: S) -> Self {
-        self.title_link = Some(title_link.into());
+    pub fn set_content_type(mut self, value: &str) -> Self {
+        self.content_type = value.into();
         self
     }
 
-    /// Change the color of attachment
+    /// Changes the header of correlation ids sent with
+    /// `send_message_with_correlation` (default X-Correlation-Id)
     ///
     /// ```
-    /// let attachment = RocketChatAttachment::new().set_color("#c97149");
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_correlation_header("X-Request-Id");
     /// ```
-    pub fn set_color<S: Into<String>>(mut self, color: S) -> Self {
-        self.color = Some(color.into());
+    pub fn set_correlation_header(mut self, name: impl Into<String>) -> Self {
+        self.correlation_header = name.into().into();
         self
     }
 
-    /// Change the author name & icon of attachment
+    /// Changes the separator between texts of messages combined by `send_combined`
+    /// (default newline)
     ///
     /// ```
-    /// let attachment = RocketChatAttachment::new().set_author("Author Name", Some("ICON_URL"));
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_join_separator("\n---\n");
     /// ```
-    pub fn set_author<S: Into<String>>(mut self, name: S, icon: Option<S>) -> Self {
-        self.author_name = Some(name.into());
-        if let Some(icon) = icon {
-            self.author_icon = Some(icon.into());
-        }
+    pub fn set_join_separator(mut self, separator: impl Into<String>) -> Self {
+        self.join_separator = separator.into().into();
         self
     }
 
-    /// Change the content of attachment
+    /// Adds a query parameter to the webhook url, for integrations expecting
+    /// an auth token in the url
+    ///
+    /// The parameter is url-encoded and appended to the query string of the
+    /// webhook url at send time, keeping its existing query parameters.
     ///
     /// ```
-    /// let attachment = RocketChatAttachment::new().set_text("Text");
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_url_token("token", "SECRET");
     /// ```
-    pub fn set_text<S: Into<String>>(mut self, text: S) -> Self {
-        self.text = Some(text.into());
+    pub fn set_url_token(mut self, param: &str, value: &str) -> Self {
+        Arc::make_mut(&mut self.url_tokens).push((param.to_string(), value.to_string()));
         self
     }
 
-    /// Change the image of attachment
+    /// Changes the timeout of a whole request, from connection to response
     ///
     /// ```
-    /// let attachment = RocketChatAttachment::new().set_image("IMAGE_URL");
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_timeout(Duration::from_secs(30));
     /// ```
-    pub fn set_image<S: Into<String>>(mut self, url: S) -> Self {
-        self.image_url = Some(url.into());
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
         self
     }
 
-    /// Change the fields of attachment
+    /// Changes the timeout of the connection to the server
+    ///
+    /// This fails fast when rocket chat is unreachable, while `set_timeout`
+    /// bounds the whole request. The async HTTP client is rebuilt, unless it
+    /// was set with `set_http_client`.
     ///
     /// ```
-    /// let attachment = RocketChatAttachment::new().set_fields(vec![Field::new()
-    ///     .set_title("Field title")
-    ///     .set_value("Field value")
-    ///     .set_short(true)]);
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_connect_timeout(Duration::from_secs(2));
     /// ```
-    pub fn set_fields(mut self, fields: Vec<Field>) -> Self {
-        self.fields = fields;
-        self
+    pub fn set_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self.rebuild_client()
     }
-}
 
-#[derive(Serialize, Default)]
-struct RocketChatMessagePayload {
-    text: Option<String>,
-    channel: Option<String>,
-    attachments: Vec<RocketChatAttachment>,
-}
-
-impl From<(RocketChatMessage, String)> for RocketChatMessagePayload {
-    fn from(message: (RocketChatMessage, String)) -> Self {
-        Self {
-            text: message.0.text,
-            channel: Some(message.1),
-            attachments: message.0.attachments,
-        }
+    /// Changes the number of retries shared by all messages of a concurrent batch
+    ///
+    /// `send_messages_concurrent` starts each call with a full budget, which is
+    /// not replenished during the call: once spent, failed sends are no longer
+    /// retried, even if they have retries left from `set_max_retries`.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_max_retries(3)
+    ///     .set_retry_budget(10);
+    /// ```
+    pub fn set_retry_budget(mut self, total_retries: u32) -> Self {
+        self.retry_budget = Some(total_retries);
+        self
     }
-}
-
-/// A structure representing a rocket chat message
-#[derive(Serialize, Default)]
-// #[serde(rename_all = "camelCase")]
-pub struct RocketChatMessage {
-    /// Text on top of attachments
-    pub text: Option<String>,
-    /// Attachments linked to message
-    pub attachments: Vec<RocketChatAttachment>,
-}
 
-impl RocketChatMessage {
-    /// Create new message
+    /// Changes the redirect policy of requests (default of reqwest, up to 10 redirects)
+    ///
+    /// The async HTTP client is rebuilt, unless it was set with `set_http_client`.
     ///
     /// ```
-    /// let message = RocketChatMessage::new();
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_redirect_policy(reqwest::redirect::Policy::limited(2));
     /// ```
-    pub fn new() -> Self {
-        RocketChatMessage::default()
+    pub fn set_redirect_policy(mut self, policy: Policy) -> Self {
+        self.redirect_policy = Some(Arc::new(policy));
+        self.rebuild_client()
     }
 
-    /// Change the content of message
+    /// Changes whether the proxy of `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`
+    /// environment variables is used (default true, like reqwest)
+    ///
+    /// When disabled, requests never go through a proxy. The async HTTP client
+    /// is rebuilt, unless it was set with `set_http_client`.
     ///
     /// ```
-    /// let message = RocketChatMessage::new().set_text("Text");
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel").use_system_proxy(false);
+    /// ```
+    pub fn use_system_proxy(mut self, enabled: bool) -> Self {
+        self.system_proxy = enabled;
+        self.rebuild_client()
+    }
+
+    /// Changes whether the JSON body is pretty-printed
+    ///
+    /// Meant for debugging, to make captured requests easier to read.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel").set_pretty_json(true);
+    /// ```
+    pub fn set_pretty_json(mut self, enabled: bool) -> Self {
+        self.pretty_json = enabled;
+        self
+    }
+
+    /// Changes the metrics recorded on each send, like successes, failures
+    /// and retries
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel").set_metrics(counters);
+    /// ```
+    pub fn set_metrics<M: Metrics + 'static>(mut self, metrics: M) -> Self {
+        self.metrics = Some(Arc::new(metrics));
+        self
+    }
+
+    /// Changes the store of the threads opened with `reply_to_key` (default in
+    /// memory, see `MemoryThreadStore`)
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel").set_thread_store(store);
+    /// ```
+    pub fn set_thread_store<S: ThreadStore + 'static>(mut self, store: S) -> Self {
+        self.thread_store = Arc::new(store);
+        self
+    }
+
+    /// Changes the format of the current time appended to the text of every
+    /// message, on a new line (default none, no timestamp)
+    ///
+    /// The time is in UTC. The format supports `%Y` (year), `%m` (month), `%d`
+    /// (day), `%H` (hour), `%M` (minute), `%S` (second) and `%%` (percent sign),
+    /// other chars are kept as is.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_append_timestamp(Some("%Y-%m-%d %H:%M:%S UTC".to_string()));
+    /// ```
+    pub fn set_append_timestamp(mut self, fmt: Option<String>) -> Self {
+        self.append_timestamp = fmt.map(Into::into);
+        self
+    }
+
+    /// Changes the key wrapping the message in the JSON body, for endpoints
+    /// expecting it under a top-level key (default none, message not wrapped)
+    ///
+    /// The message is still sent as JSON, like `{"payload": {"channel": ...}}`.
+    /// Only webhook requests are wrapped, REST API calls of `set_auth` are not.
+    ///
+    /// ```
+    /// let client = RocketChat::new("BRIDGE_WEBHOOK_URL", "#channel")
+    ///     .set_payload_key(Some("payload".to_string()));
+    /// ```
+    pub fn set_payload_key(mut self, key: Option<String>) -> Self {
+        self.payload_key = key.map(Into::into);
+        self
+    }
+
+    /// Changes the colors of attachment severities (see `RocketChatAttachment::set_severity`)
+    ///
+    /// Severities missing from the map keep their default color.
+    ///
+    /// ```
+    /// let mut colors = HashMap::new();
+    /// colors.insert(Severity::Warning, "#f2c744".to_string());
+    ///
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel").set_severity_colors(colors);
+    /// ```
+    pub fn set_severity_colors(mut self, colors: HashMap<Severity, String>) -> Self {
+        self.severity_colors = Arc::new(colors);
+        self
+    }
+
+    /// Returns the color of a severity for this client
+    pub fn severity_color(&self, severity: Severity) -> &str {
+        self.severity_colors
+            .get(&severity)
+            .map_or(severity.default_color(), String::as_str)
+    }
+
+    /// Changes the attachment appended to every message, like an environment banner
+    ///
+    /// The attachment is appended after the attachments of the message, before
+    /// the limit of `set_max_attachments` is applied. Use
+    /// `RocketChatMessage::without_default_attachment` to send a message without it.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_default_attachment(RocketChatAttachment::new().set_footer("production"));
+    /// ```
+    pub fn set_default_attachment(mut self, attachment: RocketChatAttachment) -> Self {
+        self.default_attachment = Some(Arc::new(attachment));
+        self
+    }
+
+    /// Changes the window during which an identical message sent with
+    /// `try_send_message` is skipped, to suppress duplicate alerts
+    ///
+    /// Messages are identical if their channel or room id and their content are,
+    /// before send time additions like `set_append_timestamp`. Only messages
+    /// successfully sent are remembered.
+    /// Recently sent messages are shared between clones of the client.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_dedup_window(Duration::from_secs(300));
+    /// ```
+    pub fn set_dedup_window(mut self, window: Duration) -> Self {
+        self.dedup = Some(Arc::new(DedupWindow::new(window)));
+        self
+    }
+
+    /// Changes whether bodies larger than the payload limit are rejected before
+    /// sending (default false)
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel").set_enforce_size_limit(true);
+    /// ```
+    pub fn set_enforce_size_limit(mut self, enabled: bool) -> Self {
+        self.enforce_size_limit = enabled;
+        self
+    }
+
+    /// Changes the payload limit in bytes (default `MAX_PAYLOAD_BYTES`), checked
+    /// when enabled with `set_enforce_size_limit`
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_enforce_size_limit(true)
+    ///     .set_max_payload_bytes(64 * 1024);
+    /// ```
+    pub fn set_max_payload_bytes(mut self, limit: usize) -> Self {
+        self.max_payload_bytes = limit;
+        self
+    }
+
+    /// Changes the maximum size in bytes of a response body read for the
+    /// message of an error (default `MAX_ERROR_BODY_BYTES`)
+    ///
+    /// The rest of the body is not read, and the error notes the truncation.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_max_error_body_bytes(1024);
+    /// ```
+    pub fn set_max_error_body_bytes(mut self, limit: usize) -> Self {
+        self.max_error_body_bytes = limit;
+        self
+    }
+
+    /// Changes the maximum number of attachments sent per message, the
+    /// attachments after are dropped when sending
+    ///
+    /// Rocket chat doesn't document a limit, so there is none by default. With
+    /// the `tracing` feature, a warning is logged when attachments are dropped.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel").set_max_attachments(20);
+    /// ```
+    pub fn set_max_attachments(mut self, max_attachments: usize) -> Self {
+        self.max_attachments = Some(max_attachments);
+        self
+    }
+
+    /// Send simple text message
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    ///
+    /// client.send_text("Text").await?;
+    /// ```
+    pub async fn send_text<S: Into<String>>(&self, msg: S) -> Result<reqwest::Response, Error> {
+        let msg = RocketChatMessage::new().set_text(msg.into());
+
+        self.send_message(msg).await
+    }
+
+    /// Send simple text message (sync)
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    ///
+    /// client.send_text_sync("Text");
+    /// ```
+    pub fn send_text_sync<S: Into<String>>(&self, msg: S) -> Result<Response, Error> {
+        let msg = RocketChatMessage::new().set_text(msg.into());
+
+        self.send_message_sync(msg)
+    }
+
+    /// Send a long text as multiple text messages of at most `max_chars` chars, in order
+    ///
+    /// The text is split on paragraph breaks if possible, then on line breaks,
+    /// and only in the middle of a line if it is longer than `max_chars`.
+    /// Sending stops at the first error.
+    ///
+    /// ```
+    /// let responses = client.send_text_paginated(&logs, MAX_MESSAGE_LENGTH).await?;
+    /// ```
+    pub async fn send_text_paginated(
+        &self,
+        text: &str,
+        max_chars: usize,
+    ) -> Result<Vec<reqwest::Response>, Error> {
+        let mut responses = Vec::new();
+        for page in paginate(text, max_chars)? {
+            responses.push(self.send_text(page).await?);
+        }
+        Ok(responses)
+    }
+
+    /// Send a long text as multiple text messages of at most `max_chars` chars, in order (sync)
+    ///
+    /// See `send_text_paginated`.
+    ///
+    /// ```
+    /// let responses = client.send_text_paginated_sync(&logs, MAX_MESSAGE_LENGTH)?;
+    /// ```
+    pub fn send_text_paginated_sync(
+        &self,
+        text: &str,
+        max_chars: usize,
+    ) -> Result<Vec<Response>, Error> {
+        paginate(text, max_chars)?
+            .into_iter()
+            .map(|page| self.send_text_sync(page))
+            .collect()
+    }
+
+    /// Send a code block, like a log snippet, as text message
+    ///
+    /// The body is wrapped in triple backticks with the language, and truncated
+    /// to fit in `MAX_MESSAGE_LENGTH`. Triple backticks inside the body are split
+    /// with a zero width space, so they don't end the block.
+    ///
+    /// ```
+    /// client.send_code_block("rust", &logs).await?;
+    /// ```
+    pub async fn send_code_block(
+        &self,
+        lang: &str,
+        body: &str,
+    ) -> Result<reqwest::Response, Error> {
+        self.send_text(code_block(lang, body)).await
+    }
+
+    /// Send a code block, like a log snippet, as text message (sync)
+    ///
+    /// See `send_code_block`.
+    ///
+    /// ```
+    /// client.send_code_block_sync("rust", &logs);
+    /// ```
+    pub fn send_code_block_sync(&self, lang: &str, body: &str) -> Result<Response, Error> {
+        self.send_text_sync(code_block(lang, body))
+    }
+
+    /// Send simple text message to another channel than the one of the client
+    ///
+    /// ```
+    /// client.send_text_to("#channel2", "Text").await?;
+    /// ```
+    pub async fn send_text_to<S: Into<String>>(
+        &self,
+        channel: &str,
+        msg: S,
+    ) -> Result<reqwest::Response, Error> {
+        self.clone_with_channel(channel).send_text(msg).await
+    }
+
+    /// Send simple text message to another channel than the one of the client (sync)
+    ///
+    /// ```
+    /// client.send_text_to_sync("#channel2", "Text");
+    /// ```
+    pub fn send_text_to_sync<S: Into<String>>(
+        &self,
+        channel: &str,
+        msg: S,
+    ) -> Result<Response, Error> {
+        self.clone_with_channel(channel).send_text_sync(msg)
+    }
+
+    /// Send a rocket chat message to another channel than the one of the client
+    ///
+    /// The HTTP client and the configuration of the client are used.
+    ///
+    /// ```
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// client.send_message_to("#channel2", msg).await?;
+    /// ```
+    pub async fn send_message_to(
+        &self,
+        channel: &str,
+        msg: RocketChatMessage,
+    ) -> Result<reqwest::Response, Error> {
+        self.clone_with_channel(channel).send_message(msg).await
+    }
+
+    /// Send a rocket chat message to another channel than the one of the client (sync)
+    ///
+    /// ```
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// client.send_message_to_sync("#channel2", msg);
+    /// ```
+    pub fn send_message_to_sync(
+        &self,
+        channel: &str,
+        msg: RocketChatMessage,
+    ) -> Result<Response, Error> {
+        self.clone_with_channel(channel).send_message_sync(msg)
+    }
+
+    /// Send messages read from NDJSON, one JSON message per line
+    ///
+    /// Empty lines are skipped. A line which can't be read or parsed is reported
+    /// as an error for this line, the next lines are still sent. Results are
+    /// returned with their line number, starting at 1.
+    ///
+    /// ```
+    /// let results = client.send_ndjson(std::io::stdin().lock()).await;
+    /// ```
+    pub async fn send_ndjson<R: BufRead>(
+        &self,
+        reader: R,
+    ) -> Vec<(usize, Result<reqwest::Response, Error>)> {
+        let mut results = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let res = match parse_ndjson_line(line) {
+                Some(Result::Ok(msg)) => self.send_message(msg).await,
+                Some(Err(e)) => Err(e),
+                None => continue,
+            };
+            results.push((i + 1, res));
+        }
+        results
+    }
+
+    /// Send messages read from NDJSON, one JSON message per line (sync)
+    ///
+    /// See `send_ndjson`.
+    ///
+    /// ```
+    /// let results = client.send_ndjson_sync(std::io::stdin().lock());
+    /// ```
+    pub fn send_ndjson_sync<R: BufRead>(&self, reader: R) -> Vec<(usize, Result<Response, Error>)> {
+        let mut results = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let res = match parse_ndjson_line(line) {
+                Some(Result::Ok(msg)) => self.send_message_sync(msg),
+                Some(Err(e)) => Err(e),
+                None => continue,
+            };
+            results.push((i + 1, res));
+        }
+        results
+    }
+
+    /// Send a rocket chat message to multiple channels, with the color of its
+    /// attachments overridden per channel
+    ///
+    /// Messages are sent in order of targets, and results are returned in the
+    /// same order. A message without attachments is sent without color.
+    ///
+    /// ```
+    /// let results = client
+    ///     .broadcast_with_colors(msg, &[("#alerts-prod", "#ff0000"), ("#alerts-staging", "#ffa500")])
+    ///     .await;
+    /// ```
+    pub async fn broadcast_with_colors(
+        &self,
+        msg: RocketChatMessage,
+        targets: &[(&str, &str)],
+    ) -> Vec<Result<reqwest::Response, Error>> {
+        let mut results = Vec::with_capacity(targets.len());
+        for (channel, color) in targets {
+            let mut msg = msg.clone();
+            for attachment in &mut msg.attachments {
+                attachment.color = Some(color.to_string());
+            }
+            results.push(self.send_message_to(channel, msg).await);
+        }
+        results
+    }
+
+    /// Send a rocket chat message
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// client.send_message(msg).await;
+    /// ```
+    pub async fn send_message(&self, msg: RocketChatMessage) -> Result<reqwest::Response, Error> {
+        self.send_message_retry_if(msg, |res| is_retryable(res.status()))
+            .await
+    }
+
+    /// Send a rocket chat message with a correlation id header, to find the
+    /// request in logs
+    ///
+    /// The header is set with `set_correlation_header`. With the `tracing`
+    /// feature, the send runs in a span with the correlation id.
+    ///
+    /// ```
+    /// client.send_message_with_correlation(msg, "4bf92f3577b34da6").await?;
+    /// ```
+    pub async fn send_message_with_correlation(
+        &self,
+        msg: RocketChatMessage,
+        correlation_id: &str,
+    ) -> Result<reqwest::Response, Error> {
+        let url = self.request_url()?;
+        let body = self.body(&msg)?;
+        let send = self.send_body(
+            || {
+                self.webhook_request(&url)
+                    .header(&*self.correlation_header, correlation_id)
+            },
+            body,
+            |res: &reqwest::Response| is_retryable(res.status()),
+            None,
+        );
+
+        #[cfg(feature = "tracing")]
+        let send = tracing::Instrument::instrument(
+            send,
+            tracing::info_span!("rocketchat_send", correlation_id),
+        );
+        send.await
+    }
+
+    /// Send a rocket chat message only if it is not empty
+    ///
+    /// Returns `None` without sending if the message has neither text nor
+    /// attachments, or if an identical message was sent within the window of
+    /// `set_dedup_window`. Other failures of `RocketChatMessage::validate`, like
+    /// an invalid color, are returned as errors, as well as failed sends.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    ///
+    /// if client.try_send_message(RocketChatMessage::new()).await?.is_none() {
+    ///     println!("Skipped empty message");
+    /// }
+    /// ```
+    pub async fn try_send_message(
+        &self,
+        msg: RocketChatMessage,
+    ) -> Result<Option<reqwest::Response>, Error> {
+        if msg.is_empty() {
+            return Ok(None);
+        }
+        msg.validate()?;
+        let dedup = match &self.dedup {
+            Some(dedup) => {
+                let hash = self.dedup_hash(&msg)?;
+                if dedup.is_recent(hash) {
+                    return Ok(None);
+                }
+                Some((dedup, hash))
+            }
+            None => None,
+        };
+
+        let res = self.send_message(msg).await?;
+        if let Some((dedup, hash)) = dedup {
+            dedup.insert(hash);
+        }
+        Ok(Some(res))
+    }
+
+    /// Send a rocket chat message and return how long the send took
+    ///
+    /// The elapsed time includes retries.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// let (res, elapsed) = client.send_message_timed(msg).await?;
+    /// ```
+    pub async fn send_message_timed(
+        &self,
+        msg: RocketChatMessage,
+    ) -> Result<(reqwest::Response, Duration), Error> {
+        let start = Instant::now();
+        let res = self.send_message(msg).await?;
+
+        Ok((res, start.elapsed()))
+    }
+
+    /// Send a rocket chat message before a deadline
+    ///
+    /// The remaining time until the deadline bounds the whole send, retries
+    /// included. If the deadline is already past, the message is not sent and an
+    /// error is returned, as when the deadline is reached during the send.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// client.send_message_until(msg, Instant::now() + Duration::from_secs(5)).await?;
+    /// ```
+    pub async fn send_message_until(
+        &self,
+        msg: RocketChatMessage,
+        deadline: Instant,
+    ) -> Result<reqwest::Response, Error> {
+        let remaining = deadline
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+            .ok_or_else(|| anyhow!("Deadline exceeded"))?;
+
+        tokio::time::timeout(remaining, self.send_message(msg))
+            .await
+            .map_err(|_| anyhow!("Deadline exceeded"))?
+    }
+
+    /// Send a rocket chat message, retrying when the predicate returns true
+    ///
+    /// Request errors are retried, except timeouts other than connect timeouts
+    /// since the message may have been delivered. The predicate decides whether
+    /// a response should be retried, even with a 200 status. Retries wait for
+    /// an exponential backoff (see `set_retry_backoff`) and are bounded by the
+    /// configured max retries: once exhausted, a response still matching the
+    /// predicate is returned as an error.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel").set_max_retries(3);
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// client.send_message_retry_if(msg, |res| res.status() != 200).await?;
+    /// ```
+    pub async fn send_message_retry_if<F>(
+        &self,
+        msg: RocketChatMessage,
+        should_retry: F,
+    ) -> Result<reqwest::Response, Error>
+    where
+        F: Fn(&reqwest::Response) -> bool,
+    {
+        self.send_webhook(msg, should_retry, None).await
+    }
+
+    /// Send a rocket chat message on the webhook, drawing retries from a budget
+    async fn send_webhook<F>(
+        &self,
+        msg: RocketChatMessage,
+        should_retry: F,
+        budget: Option<&RetryBudget>,
+    ) -> Result<reqwest::Response, Error>
+    where
+        F: Fn(&reqwest::Response) -> bool,
+    {
+        let url = self.request_url()?;
+        let body = self.body(&msg)?;
+
+        self.send_body(|| self.webhook_request(&url), body, should_retry, budget)
+            .await
+    }
+
+    /// Send a rocket chat message and return the status and raw bytes of the
+    /// response, for debugging
+    ///
+    /// The response is returned whatever its status, without retry, and its body
+    /// is not interpreted nor limited in size. Only request errors fail.
+    ///
+    /// ```
+    /// let (status, body) = client.send_message_raw_response(msg).await?;
+    ///
+    /// println!("{}: {:?}", status, body);
+    /// ```
+    pub async fn send_message_raw_response(
+        &self,
+        msg: RocketChatMessage,
+    ) -> Result<(reqwest::StatusCode, Vec<u8>), Error> {
+        let url = self.request_url()?;
+        let body = self.body(&msg)?;
+
+        let mut request = self.webhook_request(&url).body(body);
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let res = request
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request error: {:?}", e.status()))?;
+
+        let status = res.status();
+        let bytes = res
+            .bytes()
+            .await
+            .map_err(|e| anyhow!("Response error: {}", e))?;
+        Ok((status, bytes.to_vec()))
+    }
+
+    /// Send a rocket chat message and parse the posted message from the response
+    ///
+    /// When authentication is configured with `set_auth`, the message is posted
+    /// with the REST API `chat.postMessage` method, which returns the posted
+    /// message. Otherwise the message is posted on the webhook, which only
+    /// returns the posted message if the integration script does.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_auth("https://chat.example.com", "USER_ID", "AUTH_TOKEN");
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// let sent = client.send_message_parsed(msg).await?;
+    /// ```
+    pub async fn send_message_parsed(&self, msg: RocketChatMessage) -> Result<SentMessage, Error> {
+        let should_retry = |res: &reqwest::Response| is_retryable(res.status());
+
+        let res = match &self.auth {
+            Some(auth) => {
+                let url = auth.url("chat.postMessage");
+                let body = self.rest_body(&msg)?;
+                self.send_body(|| self.rest_request(auth, &url), body, should_retry, None)
+                    .await?
+            }
+            None => {
+                let url = self.request_url()?;
+                let body = self.body(&msg)?;
+                self.send_body(|| self.webhook_request(&url), body, should_retry, None)
+                    .await?
+            }
+        };
+
+        res.json::<PostMessageResponse>()
+            .await
+            .map_err(|e| anyhow!("Response error: {}", e))?
+            .message
+            .ok_or_else(|| anyhow!("Response error: no message in response"))
+    }
+
+    /// Send a rocket chat message, aborting the request when a token is cancelled
+    ///
+    /// When the token is cancelled before the message is sent, the request is
+    /// dropped and a `Cancelled` error is returned.
+    ///
+    /// ```
+    /// let token = CancellationToken::new();
+    ///
+    /// client.send_message_cancellable(msg, &token).await?;
+    /// ```
+    #[cfg(feature = "cancellation")]
+    pub async fn send_message_cancellable(
+        &self,
+        msg: RocketChatMessage,
+        token: &tokio_util::sync::CancellationToken,
+    ) -> Result<reqwest::Response, Error> {
+        let send = std::pin::pin!(self.send_message(msg));
+        let cancelled = std::pin::pin!(token.cancelled());
+
+        match futures_util::future::select(send, cancelled).await {
+            futures_util::future::Either::Left((res, _)) => res,
+            futures_util::future::Either::Right(_) => Err(Cancelled.into()),
+        }
+    }
+
+    /// Send a rocket chat message after a delay, in a task of the current Tokio runtime
+    ///
+    /// An error is returned if no Tokio runtime is running. The returned handle
+    /// must be kept alive until the message is sent: dropping it, or calling
+    /// `ScheduledMessage::cancel`, cancels the send.
+    ///
+    /// ```
+    /// let scheduled = client.send_message_after(msg, Duration::from_secs(600))?;
+    ///
+    /// scheduled.wait().await?;
+    /// ```
+    pub fn send_message_after(
+        &self,
+        msg: RocketChatMessage,
+        delay: Duration,
+    ) -> Result<ScheduledMessage, Error> {
+        let handle = tokio::runtime::Handle::try_current()
+            .map_err(|_| anyhow!("No Tokio runtime available"))?;
+        let client = self.clone();
+
+        let task = handle.spawn(async move {
+            tokio::time::sleep(delay).await;
+            client.send_message(msg).await
+        });
+        Ok(ScheduledMessage { task })
+    }
+
+    /// Send a rocket chat message and wait until it can be read back from the channel
+    ///
+    /// The message is posted as with `send_message_parsed`, then the REST API
+    /// `chat.getMessage` method is polled with its id until the message is
+    /// found, or an error is returned after the timeout, which bounds every poll
+    /// too. The timeout starts once the message is posted. Authentication must be
+    /// configured with `set_auth`, and the user must be allowed to read the room
+    /// of the message (usually by being a member of it).
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_auth("https://chat.example.com", "USER_ID", "AUTH_TOKEN");
+    ///
+    /// let sent = client.send_confirmed(msg, Duration::from_secs(10)).await?;
+    /// ```
+    pub async fn send_confirmed(
+        &self,
+        msg: RocketChatMessage,
+        timeout: Duration,
+    ) -> Result<SentMessage, Error> {
+        let auth = self.auth()?;
+        let sent = self.send_message_parsed(msg).await?;
+        let url = auth.url("chat.getMessage");
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            // Each poll is bounded by the time left, so a hanging server can't
+            // block past the deadline
+            let left = deadline.saturating_duration_since(Instant::now());
+            let res = auth
+                .apply(self.client.get(&url))
+                .query(&[("msgId", &sent.id)])
+                .timeout(self.timeout.map_or(left, |timeout| timeout.min(left)))
+                .send()
+                .await;
+            if let Result::Ok(res) = res {
+                if res.status().is_success() {
+                    let found = res
+                        .json::<PostMessageResponse>()
+                        .await
+                        .is_ok_and(|res| res.message.is_some());
+                    if found {
+                        return Ok(sent);
+                    }
+                }
+            }
+            if Instant::now() + CONFIRM_INTERVAL > deadline {
+                bail!("Message {} not confirmed after {:?}", sent.id, timeout);
+            }
+            tokio::time::sleep(CONFIRM_INTERVAL).await;
+        }
+    }
+
+    /// Combine multiple messages into one message and send it
+    ///
+    /// Messages are merged in order with `RocketChatMessage::merge_with` and the
+    /// configured join separator, and the
+    /// posted message is parsed as with `send_message_parsed`. If rocket chat
+    /// stored fewer attachments than sent, a `PartialDelivery` error listing the
+    /// attachments which may have been dropped is returned.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_auth("https://chat.example.com", "USER_ID", "AUTH_TOKEN");
+    ///
+    /// let msgs = vec![
+    ///    RocketChatMessage::new().set_text("Text"),
+    ///    RocketChatMessage::new().set_text("Text2"),
+    /// ];
+    ///
+    /// if let Err(e) = client.send_combined(msgs).await {
+    ///     if let Some(partial) = e.downcast_ref::<PartialDelivery>() {
+    ///         println!("Dropped attachments: {:?}", partial.dropped);
+    ///     }
+    /// }
+    /// ```
+    pub async fn send_combined(&self, msgs: Vec<RocketChatMessage>) -> Result<SentMessage, Error> {
+        let msg = msgs
+            .into_iter()
+            .fold(RocketChatMessage::new(), |msg, other| {
+                msg.merge_with(other, &self.join_separator)
+            });
+        let sent_attachments = msg.attachments.len();
+
+        let message = self.send_message_parsed(msg).await?;
+
+        match message.attachments.as_ref().map(Vec::len) {
+            Some(stored) if stored < sent_attachments => Err(PartialDelivery {
+                message,
+                dropped: (stored..sent_attachments).collect(),
+            }
+            .into()),
+            _ => Ok(message),
+        }
+    }
+
+    /// Send a rocket chat message as a reply in the thread of a parent message
+    ///
+    /// The parent message id is returned by `send_message_parsed`.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_auth("https://chat.example.com", "USER_ID", "AUTH_TOKEN");
+    /// let parent = client.send_message_parsed(RocketChatMessage::new().set_text("Alert")).await?;
+    ///
+    /// client.reply_to(&parent.id, RocketChatMessage::new().set_text("Update")).await?;
+    /// ```
+    pub async fn reply_to(
+        &self,
+        parent_message_id: &str,
+        msg: RocketChatMessage,
+    ) -> Result<reqwest::Response, Error> {
+        self.send_message(msg.set_tmid(parent_message_id)).await
+    }
+
+    /// Send a rocket chat message in the thread of a key, like an incident id
+    ///
+    /// The first message of a key is sent as the parent of a new thread, with
+    /// `send_message_parsed` to get its id, so authentication must be configured
+    /// with `set_auth` (unless the webhook integration script returns the posted
+    /// message). The next messages of the key are replies in this thread. The
+    /// parent message id is returned.
+    ///
+    /// Threads are kept in memory by default, so they are lost when the process
+    /// exits: use `set_thread_store` to persist them. Concurrent first messages
+    /// of a same key may open several threads.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_auth("https://chat.example.com", "USER_ID", "AUTH_TOKEN");
+    ///
+    /// client.reply_to_key("INC-42", RocketChatMessage::new().set_text("Database down")).await?;
+    /// client.reply_to_key("INC-42", RocketChatMessage::new().set_text("Failover done")).await?;
+    /// ```
+    pub async fn reply_to_key(&self, key: &str, msg: RocketChatMessage) -> Result<String, Error> {
+        if let Some(parent_message_id) = self.thread_store.get(key) {
+            self.reply_to(&parent_message_id, msg).await?;
+            return Ok(parent_message_id);
+        }
+
+        let parent = self.send_message_parsed(msg).await?;
+        self.thread_store.insert(key, parent.id.clone());
+        Ok(parent.id)
+    }
+
+    /// Send a rocket chat message and return its permalink
+    ///
+    /// The message is sent with `send_message_parsed` to get its id, so
+    /// authentication must be configured with `set_auth`, unless the webhook
+    /// integration script returns the posted message. The permalink is built
+    /// from the server url (see `set_server_url`) and the channel of the client,
+    /// so a channel is required.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_auth("https://chat.example.com", "USER_ID", "AUTH_TOKEN");
+    ///
+    /// let (sent, permalink) = client.send_with_permalink(msg).await?;
+    /// ```
+    pub async fn send_with_permalink(
+        &self,
+        msg: RocketChatMessage,
+    ) -> Result<(SentMessage, String), Error> {
+        // Checked before sending, to not post a message without permalink
+        let room = self.permalink_room()?;
+        let server_url = self.permalink_server_url()?;
+        let sent = self.send_message_parsed(msg).await?;
+
+        let permalink = format!(
+            "{}/{}?msg={}",
+            server_url.trim_end_matches('/'),
+            room,
+            sent.id
+        );
+        Ok((sent, permalink))
+    }
+
+    /// Returns the path of the channel in permalinks
+    fn permalink_room(&self) -> Result<String, Error> {
+        let channel = self
+            .channel
+            .as_deref()
+            .ok_or_else(|| anyhow!("Permalink requires a channel"))?;
+        Ok(match channel.strip_prefix('@') {
+            Some(user) => format!("direct/{}", user),
+            None => format!("channel/{}", channel.trim_start_matches('#')),
+        })
+    }
+
+    /// Returns the server url of permalinks
+    fn permalink_server_url(&self) -> Result<String, Error> {
+        if let Some(server_url) = &self.server_url {
+            return Ok(server_url.to_string());
+        }
+        if let Some(auth) = &self.auth {
+            return Ok(auth.server_url.clone());
+        }
+        let url = reqwest::Url::parse(&self.webhook_url)
+            .map_err(|e| anyhow!("Invalid webhook url: {}", e))?;
+        Ok(url.origin().ascii_serialization())
+    }
+
+    /// Send a parent message and return a handle to reply in its thread
+    ///
+    /// The parent message is sent with `send_message_parsed` to get its id, so
+    /// authentication must be configured with `set_auth`, unless the webhook
+    /// integration script returns the posted message.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_auth("https://chat.example.com", "USER_ID", "AUTH_TOKEN");
+    ///
+    /// let thread = client.open_thread(RocketChatMessage::new().set_text("Incident")).await?;
+    /// thread.reply(RocketChatMessage::new().set_text("Update")).await?;
+    /// ```
+    pub async fn open_thread(&self, parent_msg: RocketChatMessage) -> Result<ThreadHandle, Error> {
+        let parent = self.send_message_parsed(parent_msg).await?;
+
+        Ok(ThreadHandle::new(self.clone(), parent.id))
+    }
+
+    /// Add a reaction to a posted message
+    ///
+    /// Reactions use the REST API `chat.react` method, so authentication must be
+    /// configured with `set_auth`, the webhook can't be used. The message id is
+    /// returned by `send_message_parsed`.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel")
+    ///     .set_auth("https://chat.example.com", "USER_ID", "AUTH_TOKEN");
+    /// let sent = client.send_message_parsed(RocketChatMessage::new().set_text("Text")).await?;
+    ///
+    /// client.react(&sent.id, Reaction::Fire).await?;
+    /// ```
+    pub async fn react<R: Into<Reaction>>(
+        &self,
+        message_id: &str,
+        reaction: R,
+    ) -> Result<reqwest::Response, Error> {
+        let auth = self.auth()?;
+        let url = auth.url("chat.react");
+        let body = serde_json::to_vec(&serde_json::json!({
+            "messageId": message_id,
+            "emoji": reaction.into().as_str(),
+        }))
+        .map_err(|e| anyhow!("Serialization error: {}", e))?
+        .into();
+
+        self.send_body(
+            || self.rest_request(auth, &url),
+            body,
+            |res: &reqwest::Response| is_retryable(res.status()),
+            None,
+        )
+        .await
+    }
+
+    /// Returns a POST request to a REST API method, with authentication
+    fn rest_request(&self, auth: &RestAuth, url: &str) -> reqwest::RequestBuilder {
+        auth.apply(self.client.post(url))
+            .header(CONTENT_TYPE, "application/json")
+    }
+
+    /// Send a body, retrying when the predicate returns true
+    ///
+    /// The request sets the content type, see `webhook_request` and `rest_request`.
+    async fn send_body<R, F>(
+        &self,
+        request: R,
+        body: Bytes,
+        should_retry: F,
+        budget: Option<&RetryBudget>,
+    ) -> Result<reqwest::Response, Error>
+    where
+        R: Fn() -> reqwest::RequestBuilder,
+        F: Fn(&reqwest::Response) -> bool,
+    {
+        let mut retries = 0;
+        loop {
+            let mut request = request().body(body.clone());
+            if let Some(timeout) = self.timeout {
+                request = request.timeout(timeout);
+            }
+            let res = request.send().await;
+
+            let retry = res.as_ref().map_or_else(is_retryable_error, &should_retry);
+            if retry && retries < self.max_retries && budget.is_none_or(RetryBudget::take) {
+                retries += 1;
+                self.record_retry();
+                tokio::time::sleep(self.retry_delay(retries)).await;
+                continue;
+            }
+
+            let res = match res {
+                Err(e) => Err(anyhow!("Request error: {:?}", e.status())),
+                Result::Ok(res) if res.status() == 200 && !retry => Ok(res),
+                // Manage error if status is not 200
+                Result::Ok(res) => {
+                    let status = res.status();
+                    let body = self.read_error_body(res).await;
+                    Err(response_error(status, body, retry))
+                }
+            };
+            return self.record(res);
+        }
+    }
+
+    /// Send a value convertible to a rocket chat message
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    /// let alert = Alert { name: "CPU".to_string() };
+    ///
+    /// client.send(&alert).await?;
+    /// ```
+    pub async fn send<T: ToRocketChatMessage + ?Sized>(
+        &self,
+        value: &T,
+    ) -> Result<reqwest::Response, Error> {
+        self.send_message(value.to_message()).await
+    }
+
+    /// Send a value convertible to a rocket chat message (sync)
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    /// let alert = Alert { name: "CPU".to_string() };
+    ///
+    /// client.send_sync(&alert);
+    /// ```
+    pub fn send_sync<T: ToRocketChatMessage + ?Sized>(&self, value: &T) -> Result<Response, Error> {
+        self.send_message_sync(value.to_message())
+    }
+
+    /// Send a rocket chat message (sync)
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// client.send_message_sync(msg);
+    /// ```
+    pub fn send_message_sync(&self, msg: RocketChatMessage) -> Result<Response, Error> {
+        self.send_message_retry_if_sync(msg, |res| is_retryable(res.status()))
+    }
+
+    /// Send a rocket chat message, retrying when the predicate returns true (sync)
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel").set_max_retries(3);
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// client.send_message_retry_if_sync(msg, |res| res.status() != 200);
+    /// ```
+    pub fn send_message_retry_if_sync<F>(
+        &self,
+        msg: RocketChatMessage,
+        should_retry: F,
+    ) -> Result<Response, Error>
+    where
+        F: Fn(&Response) -> bool,
+    {
+        let client = self.blocking_client()?;
+
+        let url = self.request_url()?;
+        let body = self.body(&msg)?;
+
+        let mut retries = 0;
+        loop {
+            let mut request = client
+                .post(url.clone())
+                .header(CONTENT_TYPE, &*self.content_type)
+                .body(body.clone());
+            if let Some(timeout) = self.timeout {
+                request = request.timeout(timeout);
+            }
+            if let Some((user, pass)) = self.basic_auth.as_deref() {
+                request = request.basic_auth(user, pass.as_ref());
+            }
+            let res = request.send();
+
+            let retry = res.as_ref().map_or_else(is_retryable_error, &should_retry);
+            if retry && retries < self.max_retries {
+                retries += 1;
+                self.record_retry();
+                std::thread::sleep(self.retry_delay(retries));
+                continue;
+            }
+
+            let res = match res {
+                Err(e) => Err(anyhow!("Request error: {:?}", e.status())),
+                Result::Ok(res) if res.status() == 200 && !retry => Ok(res),
+                // Manage error if status is not 200
+                Result::Ok(res) => {
+                    let status = res.status();
+                    let body = self.read_error_body_sync(res);
+                    Err(response_error(status, body, retry))
+                }
+            };
+            return self.record(res);
+        }
+    }
+
+    /// Send a rocket chat message from sync code running inside a Tokio runtime
+    ///
+    /// Unlike `send_message_sync`, the async client is reused by blocking on
+    /// `send_message`. This requires the current thread to run within a
+    /// multi-threaded Tokio runtime, otherwise an error is returned and
+    /// `send_message_sync` should be used.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// client.send_message_blocking(msg)?;
+    /// ```
+    pub fn send_message_blocking(
+        &self,
+        msg: RocketChatMessage,
+    ) -> Result<reqwest::Response, Error> {
+        let handle = tokio::runtime::Handle::try_current()
+            .map_err(|_| anyhow!("No Tokio runtime available, use send_message_sync"))?;
+
+        if handle.runtime_flavor() != tokio::runtime::RuntimeFlavor::MultiThread {
+            bail!("A multi-threaded Tokio runtime is required, use send_message_sync");
+        }
+        tokio::task::block_in_place(|| handle.block_on(self.send_message(msg)))
+    }
+
+    /// Send multiple messages at the same time on the same channel
+    ///
+    /// All messages are sent even if some fail, see `BatchResult`.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    ///
+    /// let msgs = vec![
+    ///    RocketChatMessage::new().set_text("Text"),
+    ///    RocketChatMessage::new().set_text("Text2"),
+    /// ];
+    ///
+    /// client.send_messages(msgs).await.into_result()?;
+    /// ```
+    pub async fn send_messages(&self, msgs: Vec<RocketChatMessage>) -> BatchResult {
+        let mut result = BatchResult::default();
+        for (i, msg) in msgs.into_iter().enumerate() {
+            result.push(i, self.send_message(msg).await);
+        }
+        result
+    }
+
+    /// Send multiple messages concurrently on the same channel
+    ///
+    /// At most `limit` messages are sent at the same time, so messages may be
+    /// posted out of order. All messages are sent even if some fail, and errors
+    /// are sorted by message index. Retries are drawn from the retry budget, if set.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    ///
+    /// let msgs = vec![
+    ///    RocketChatMessage::new().set_text("Text"),
+    ///    RocketChatMessage::new().set_text("Text2"),
+    /// ];
+    ///
+    /// let result = client.send_messages_concurrent(msgs, 4).await;
+    /// ```
+    pub async fn send_messages_concurrent(
+        &self,
+        msgs: Vec<RocketChatMessage>,
+        limit: usize,
+    ) -> BatchResult {
+        let budget = self.retry_budget.map(RetryBudget::new);
+        let should_retry = |res: &reqwest::Response| is_retryable(res.status());
+
+        let mut results = stream::iter(msgs.into_iter().enumerate())
+            .map(|(i, msg)| {
+                let send = self.send_webhook(msg, should_retry, budget.as_ref());
+                async move { (i, send.await) }
+            })
+            .buffer_unordered(limit.max(1));
+
+        let mut result = BatchResult::default();
+        while let Some((i, res)) = results.next().await {
+            result.push(i, res);
+        }
+        result.errors.sort_by_key(|(i, _)| *i);
+        result
+    }
+
+    /// Send multiple messages on the same channel, reporting progress
+    ///
+    /// `progress` is called with the number of processed messages, sent or
+    /// failed, and the total number of messages after each send.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    ///
+    /// let msgs = vec![
+    ///    RocketChatMessage::new().set_text("Text"),
+    ///    RocketChatMessage::new().set_text("Text2"),
+    /// ];
+    ///
+    /// client
+    ///     .send_messages_with_progress(msgs, |done, total| println!("{}/{}", done, total))
+    ///     .await;
+    /// ```
+    pub async fn send_messages_with_progress<F: FnMut(usize, usize)>(
+        &self,
+        msgs: Vec<RocketChatMessage>,
+        mut progress: F,
+    ) -> BatchResult {
+        let total = msgs.len();
+        let mut result = BatchResult::default();
+        for (i, msg) in msgs.into_iter().enumerate() {
+            result.push(i, self.send_message(msg).await);
+            progress(i + 1, total);
+        }
+        result
+    }
+
+    /// Send multiple messages at the same time on the same channel (sync)
+    ///
+    /// All messages are sent even if some fail, see `BatchResult`.
+    ///
+    /// ```
+    /// let client = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel");
+    ///
+    /// let msgs = vec![
+    ///    RocketChatMessage::new().set_text("Text"),
+    ///    RocketChatMessage::new().set_text("Text2"),
+    /// ];
+    ///
+    /// client.send_messages_sync(msgs);
+    /// ```
+    pub fn send_messages_sync(&self, msgs: Vec<RocketChatMessage>) -> BatchResult {
+        let mut result = BatchResult::default();
+        for (i, msg) in msgs.into_iter().enumerate() {
+            result.push(i, self.send_message_sync(msg));
+        }
+        result
+    }
+
+    /// Returns the webhook url with the configured query parameters
+    fn request_url(&self) -> Result<reqwest::Url, Error> {
+        let mut url = reqwest::Url::parse(&self.webhook_url)
+            .map_err(|e| anyhow!("Invalid webhook url: {}", e))?;
+
+        if !self.url_tokens.is_empty() {
+            url.query_pairs_mut().extend_pairs(self.url_tokens.iter());
+        }
+        Ok(url)
+    }
+
+    /// Returns a request to the webhook, with the content type and the basic
+    /// authentication if set
+    fn webhook_request(&self, url: &reqwest::Url) -> reqwest::RequestBuilder {
+        let request = self
+            .client
+            .post(url.clone())
+            .header(CONTENT_TYPE, &*self.content_type);
+        match self.basic_auth.as_deref() {
+            Some((user, pass)) => request.basic_auth(user, pass.as_ref()),
+            None => request,
+        }
+    }
+
+    /// Rebuilds the async HTTP client from the configuration, keeping a client
+    /// set with `set_http_client`
+    fn rebuild_client(mut self) -> Self {
+        if self.custom_client {
+            return self;
+        }
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if !self.system_proxy {
+            builder = builder.no_proxy();
+        }
+        if let Some(policy) = self.shared_redirect_policy() {
+            builder = builder.redirect(policy);
+        }
+        // Like `reqwest::Client::new`, only fails if the TLS backend can't be initialized
+        self.client = Arc::new(builder.build().expect("TLS backend cannot be initialized"));
+        self
+    }
+
+    /// Returns a redirect policy delegating to the configured one, which can't be cloned
+    fn shared_redirect_policy(&self) -> Option<Policy> {
+        self.redirect_policy
+            .clone()
+            .map(|policy| Policy::custom(move |attempt| policy.redirect(attempt)))
+    }
+
+    /// Spawn a background thread sending messages with the blocking client, for
+    /// fire-and-forget sends in sync code
+    ///
+    /// ```
+    /// let worker = RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel").spawn_sync_worker()?;
+    ///
+    /// worker.send(RocketChatMessage::new().set_text("Text"))?;
+    /// worker.join()?.into_result()?;
+    /// ```
+    pub fn spawn_sync_worker(self) -> Result<SyncSender, Error> {
+        SyncSender::spawn(self)
+    }
+
+    /// Builds a blocking HTTP client from the configuration
+    fn blocking_client(&self) -> Result<reqwest::blocking::Client, Error> {
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if !self.system_proxy {
+            builder = builder.no_proxy();
+        }
+        if let Some(policy) = self.shared_redirect_policy() {
+            builder = builder.redirect(policy);
+        }
+        builder.build().map_err(|e| anyhow!("Client error: {}", e))
+    }
+
+    /// Returns the authentication for REST API calls
+    fn auth(&self) -> Result<&RestAuth, Error> {
+        self.auth
+            .as_deref()
+            .ok_or_else(|| anyhow!("Authentication required: use set_auth"))
+    }
+
+    /// Records the result of a send in the metrics
+    fn record<T>(&self, res: Result<T, Error>) -> Result<T, Error> {
+        if let Some(metrics) = &self.metrics {
+            match &res {
+                Result::Ok(_) => metrics.on_success(),
+                Err(e) => metrics.on_failure(e),
+            }
+        }
+        res
+    }
+
+    /// Returns the delay before a retry, starting at 1
+    fn retry_delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.retry_backoff
+            .saturating_mul(factor)
+            .min(MAX_RETRY_DELAY)
+    }
+
+    /// Records a retry in the metrics
+    fn record_retry(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.on_retry();
+        }
+    }
+
+    /// Returns the JSON body sent to the webhook for a message, with the channel
+    /// of the client and its send time additions, like for snapshot tests
+    ///
+    /// ```
+    /// let body = client.webhook_body(&RocketChatMessage::new().set_text("Text"))?;
+    ///
+    /// assert_eq!(&body[..], br##"{"channel":"#channel","text":"Text","attachments":[]}"##);
+    /// ```
+    pub fn webhook_body(&self, msg: &RocketChatMessage) -> Result<Bytes, Error> {
+        self.body(msg)
+    }
+
+    /// Serializes a message into the JSON body sent to the webhook, wrapped
+    /// under the payload key if configured
+    ///
+    /// The body is shared between retries without being copied.
+    fn body(&self, msg: &RocketChatMessage) -> Result<Bytes, Error> {
+        self.encode(msg, self.payload_key.as_deref())
+    }
+
+    /// Serializes a message into the JSON body sent to the REST API, never
+    /// wrapped since rocket chat expects the message at the top level
+    fn rest_body(&self, msg: &RocketChatMessage) -> Result<Bytes, Error> {
+        self.encode(msg, None)
+    }
+
+    /// Serializes a message into a JSON body, optionally wrapped under a key
+    fn encode(&self, msg: &RocketChatMessage, payload_key: Option<&str>) -> Result<Bytes, Error> {
+        msg.check_extras()?;
+        let mut msg = Cow::Borrowed(msg);
+        if let Some(fmt) = &self.append_timestamp {
+            let timestamp = format_timestamp(fmt, SystemTime::now());
+            let text = &mut msg.to_mut().text;
+            *text = Some(match text.take() {
+                Some(text) => format!("{}\n{}", text, timestamp),
+                None => timestamp,
+            });
+        }
+        if let Some(attachment) = &self.default_attachment {
+            if !msg.skip_default_attachment {
+                msg.to_mut()
+                    .attachments
+                    .push(RocketChatAttachment::clone(attachment));
+            }
+        }
+        if msg
+            .attachments
+            .iter()
+            .any(|attachment| attachment.color.is_none() && attachment.severity.is_some())
+        {
+            for attachment in &mut msg.to_mut().attachments {
+                if let (None, Some(severity)) = (&attachment.color, attachment.severity) {
+                    attachment.color = Some(self.severity_color(severity).to_string());
+                }
+            }
+        }
+        match self.max_attachments {
+            Some(max) if msg.attachments.len() > max => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    "Dropping {} attachments over the limit of {}",
+                    msg.attachments.len() - max,
+                    max
+                );
+                msg.to_mut().attachments.truncate(max);
+            }
+            _ => {}
+        }
+        let msg = RocketChatMessagePayload {
+            channel: self.channel.as_deref(),
+            room_id: self.room_id.as_deref(),
+            message: &msg,
+        };
+
+        BODY_BUFFER.with(|buffer| {
+            // The buffer is kept between bodies, so it only grows for the
+            // first large ones, and the body is copied at its exact size
+            let mut buffer = buffer.borrow_mut();
+            buffer.clear();
+            let res = match payload_key {
+                Some(key) => self.write_json(&mut buffer, &HashMap::from([(key, &msg)])),
+                None => self.write_json(&mut buffer, &msg),
+            };
+            res.map_err(|e| anyhow!("Serialization error: {}", e))?;
+            if self.enforce_size_limit && buffer.len() > self.max_payload_bytes {
+                bail!(
+                    "Payload too large: {} bytes, limit is {}",
+                    buffer.len(),
+                    self.max_payload_bytes
+                );
+            }
+            let body = Bytes::copy_from_slice(&buffer);
+            buffer.clear();
+            buffer.shrink_to(BODY_BUFFER_MAX_CAPACITY);
+            Ok(body)
+        })
+    }
+
+    /// Hashes the target and content of a message for the dedup window,
+    /// without the timestamp added at send time so identical messages match
+    fn dedup_hash(&self, msg: &RocketChatMessage) -> Result<u64, Error> {
+        let payload = RocketChatMessagePayload {
+            channel: self.channel.as_deref(),
+            room_id: self.room_id.as_deref(),
+            message: msg,
+        };
+        let body =
+            serde_json::to_vec(&payload).map_err(|e| anyhow!("Serialization error: {}", e))?;
+        Ok(DedupWindow::hash(&body))
+    }
+
+    /// Reads the body of a failed response, up to `max_error_body_bytes`
+    async fn read_error_body(&self, mut res: reqwest::Response) -> ErrorBody {
+        let mut body = ErrorBody::new(self.max_error_body_bytes);
+        while let Result::Ok(Some(chunk)) = res.chunk().await {
+            if !body.push(&chunk) {
+                break;
+            }
+        }
+        body
+    }
+
+    /// Reads the body of a failed response, up to `max_error_body_bytes` (sync)
+    fn read_error_body_sync(&self, res: Response) -> ErrorBody {
+        let mut body = ErrorBody::new(self.max_error_body_bytes);
+        // One more byte is read to know if the body is truncated
+        let mut bytes = Vec::new();
+        let limit = self.max_error_body_bytes.saturating_add(1) as u64;
+        let _ = res.take(limit).read_to_end(&mut bytes);
+        body.push(&bytes);
+        body
+    }
+
+    /// Serializes a value to the body, pretty printed if configured
+    fn write_json<T: Serialize>(&self, body: &mut Vec<u8>, value: &T) -> serde_json::Result<()> {
+        if self.pretty_json {
+            serde_json::to_writer_pretty(body, value)
+        } else {
+            serde_json::to_writer(body, value)
+        }
+    }
+}
+
+/// Hashes of recently sent messages with their send time
+struct DedupWindow {
+    window: Duration,
+    sent: Mutex<HashMap<u64, Instant>>,
+}
+
+impl DedupWindow {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn hash(body: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns true if a message was sent within the window, forgetting older messages
+    fn is_recent(&self, hash: u64) -> bool {
+        let mut sent = self.sent.lock().unwrap_or_else(|e| e.into_inner());
+        sent.retain(|_, time| time.elapsed() < self.window);
+        sent.contains_key(&hash)
+    }
+
+    fn insert(&self, hash: u64) {
+        let mut sent = self.sent.lock().unwrap_or_else(|e| e.into_inner());
+        sent.insert(hash, Instant::now());
+    }
+}
+
+/// Number of retries left, shared by concurrent sends
+struct RetryBudget(AtomicU32);
+
+impl RetryBudget {
+    fn new(total_retries: u32) -> Self {
+        Self(AtomicU32::new(total_retries))
+    }
+
+    /// Takes a retry from the budget, returns false if the budget is spent
+    fn take(&self) -> bool {
+        self.0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok()
+    }
+}
+
+/// Authentication for rocket chat REST API calls
+#[derive(Clone)]
+struct RestAuth {
+    /// Base url of rocket chat server
+    server_url: String,
+    /// Id of the user
+    user_id: String,
+    /// Personal access token of the user
+    auth_token: String,
+}
+
+impl RestAuth {
+    /// Returns the url of a REST API method
+    fn url(&self, method: &str) -> String {
+        format!(
+            "{}/api/v1/{}",
+            self.server_url.trim_end_matches('/'),
+            method
+        )
+    }
+
+    /// Adds authentication headers to a request
+    fn apply(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        request
+            .header("X-User-Id", &self.user_id)
+            .header("X-Auth-Token", &self.auth_token)
+    }
+}
+
+impl fmt::Debug for RocketChat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let webhook_url = match reqwest::Url::parse(&self.webhook_url) {
+            Result::Ok(url) => format!("{}://{}/***", url.scheme(), url.host_str().unwrap_or("")),
+            Err(_) => "***".to_string(),
+        };
+        let url_tokens: Vec<&str> = self
+            .url_tokens
+            .iter()
+            .map(|(param, _)| param.as_str())
+            .collect();
+
+        f.debug_struct("RocketChat")
+            .field("webhook_url", &webhook_url)
+            .field("server_url", &self.server_url)
+            .field("channel", &self.channel)
+            .field("room_id", &self.room_id)
+            .field("max_retries", &self.max_retries)
+            .field("retry_backoff", &self.retry_backoff)
+            .field("retry_budget", &self.retry_budget)
+            .field("pretty_json", &self.pretty_json)
+            .field("max_attachments", &self.max_attachments)
+            .field("default_attachment", &self.default_attachment.is_some())
+            .field("append_timestamp", &self.append_timestamp)
+            .field("payload_key", &self.payload_key)
+            .field(
+                "dedup_window",
+                &self.dedup.as_ref().map(|dedup| dedup.window),
+            )
+            .field("severity_colors", &self.severity_colors)
+            .field("enforce_size_limit", &self.enforce_size_limit)
+            .field("max_payload_bytes", &self.max_payload_bytes)
+            .field("max_error_body_bytes", &self.max_error_body_bytes)
+            .field("content_type", &self.content_type)
+            .field("correlation_header", &self.correlation_header)
+            .field("join_separator", &self.join_separator)
+            .field("url_tokens", &url_tokens)
+            .field("timeout", &self.timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("system_proxy", &self.system_proxy)
+            .field("custom_client", &self.custom_client)
+            .field("auth", &self.auth)
+            .field("basic_auth", &self.basic_auth.as_ref().map(|_| "***"))
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for RestAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RestAuth")
+            .field("server_url", &self.server_url)
+            .field("user_id", &self.user_id)
+            .field("auth_token", &"***")
+            .finish()
+    }
+}
+
+/// A structure representing a message posted on rocket chat
+#[derive(Debug, Clone, Deserialize)]
+pub struct SentMessage {
+    /// Id of message
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// Id of the room of message
+    pub rid: String,
+    /// Attachments of message, as stored by rocket chat
+    #[serde(default)]
+    pub attachments: Option<Vec<serde_json::Value>>,
+}
+
+/// An error returned when rocket chat stored fewer attachments than sent
+#[derive(Debug, Clone)]
+pub struct PartialDelivery {
+    /// Message posted on rocket chat
+    pub message: SentMessage,
+    /// Indexes of the attachments which may have been dropped
+    pub dropped: Vec<usize>,
+}
+
+impl fmt::Display for PartialDelivery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Partial delivery: attachments {:?} may have been dropped",
+            self.dropped
+        )
+    }
+}
+
+impl std::error::Error for PartialDelivery {}
+
+/// Error returned when a send is cancelled, see `RocketChat::send_message_cancellable`
+#[cfg(feature = "cancellation")]
+#[derive(Debug, Clone, Copy)]
+pub struct Cancelled;
+
+#[cfg(feature = "cancellation")]
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Send cancelled")
+    }
+}
+
+#[cfg(feature = "cancellation")]
+impl std::error::Error for Cancelled {}
+
+/// A usage of a message ignored or unsupported by rocket chat, returned by
+/// `RocketChatMessage::lint`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    /// Index of the attachment concerned, if not the message itself
+    pub attachment: Option<usize>,
+    /// Description of the lint
+    pub message: String,
+}
+
+impl Lint {
+    /// Creates a lint of the message itself
+    fn message<S: Into<String>>(message: S) -> Self {
+        Self {
+            attachment: None,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a lint of an attachment
+    fn attachment<S: Into<String>>(index: usize, message: S) -> Self {
+        Self {
+            attachment: Some(index),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.attachment {
+            Some(i) => write!(f, "Attachment {}: {}", i, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// A structure summarizing the sends of multiple messages
+#[derive(Debug, Default)]
+#[must_use]
+pub struct BatchResult {
+    /// Number of messages sent
+    pub sent: usize,
+    /// Number of messages which failed
+    pub failed: usize,
+    /// Errors with the index of their message
+    pub errors: Vec<(usize, Error)>,
+}
+
+impl BatchResult {
+    /// Returns true if all messages were sent
+    pub fn is_complete_success(&self) -> bool {
+        self.failed == 0
+    }
+
+    /// Returns the error of the first failed message, if any
+    ///
+    /// ```
+    /// client.send_messages(msgs).await.into_result()?;
+    /// ```
+    pub fn into_result(self) -> Result<(), Error> {
+        match self.errors.into_iter().next() {
+            Some((_, e)) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Records the result of a message send
+    fn push<T>(&mut self, index: usize, res: Result<T, Error>) {
+        match res {
+            Result::Ok(_) => self.sent += 1,
+            Err(e) => {
+                self.failed += 1;
+                self.errors.push((index, e));
+            }
+        }
+    }
+}
+
+/// A structure representing a message scheduled with `RocketChat::send_message_after`
+///
+/// The send is cancelled when the structure is dropped.
+#[derive(Debug)]
+pub struct ScheduledMessage {
+    /// Task sending the message
+    task: tokio::task::JoinHandle<Result<reqwest::Response, Error>>,
+}
+
+impl ScheduledMessage {
+    /// Cancel the send, if the message is not sent yet
+    pub fn cancel(self) {
+        self.task.abort();
+    }
+
+    /// Wait until the message is sent
+    pub async fn wait(mut self) -> Result<reqwest::Response, Error> {
+        (&mut self.task)
+            .await
+            .map_err(|e| anyhow!("Scheduled send failed: {}", e))?
+    }
+}
+
+impl Drop for ScheduledMessage {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Deserialize)]
+struct PostMessageResponse {
+    message: Option<SentMessage>,
+}
+
+/// Returns the total number of attachments of multiple messages
+///
+/// ```
+/// let msgs = vec![
+///    RocketChatMessage::new().set_attachments(vec![RocketChatAttachment::new()]),
+///    RocketChatMessage::new().set_text("Text"),
+/// ];
+///
+/// assert_eq!(total_attachments(&msgs), 1);
+/// ```
+pub fn total_attachments(msgs: &[RocketChatMessage]) -> usize {
+    msgs.iter().map(|msg| msg.attachments.len()).sum()
+}
+
+/// Maximum number of chars of a message text allowed by default by rocket chat
+/// (`Message_MaxAllowedSize` setting)
+pub const MAX_MESSAGE_LENGTH: usize = 5000;
+
+/// Default limit of a request body in bytes, used by size checks
+///
+/// The actual limit depends on the rocket chat server and its reverse proxy,
+/// this is a conservative value accepted by default setups.
+pub const MAX_PAYLOAD_BYTES: usize = 1024 * 1024;
+
+/// Default delay before the first retry of a send
+pub const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Maximum delay between two retries of a send
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Default maximum size in bytes of a response body read for an error
+pub const MAX_ERROR_BODY_BYTES: usize = 8 * 1024;
+
+/// Initial capacity of the buffer a body is serialized into, enough for most messages
+const BODY_CAPACITY: usize = 512;
+
+/// Capacity kept by the serialization buffer after a body, so a huge body
+/// doesn't hold memory for the lifetime of the thread
+const BODY_BUFFER_MAX_CAPACITY: usize = 64 * 1024;
+
+thread_local! {
+    /// Buffer bodies are serialized into, reused by the sends of a thread
+    static BODY_BUFFER: std::cell::RefCell<Vec<u8>> =
+        std::cell::RefCell::new(Vec::with_capacity(BODY_CAPACITY));
+}
+
+/// Interval between checks of a sent message in `send_confirmed`
+const CONFIRM_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Body of a failed response, read up to a limit
+struct ErrorBody {
+    /// Bytes read, at most the limit
+    bytes: Vec<u8>,
+    /// Maximum number of bytes kept
+    limit: usize,
+    /// Whether bytes over the limit were dropped
+    truncated: bool,
+}
+
+impl ErrorBody {
+    fn new(limit: usize) -> Self {
+        Self {
+            bytes: Vec::new(),
+            limit,
+            truncated: false,
+        }
+    }
+
+    /// Appends bytes up to the limit, returns false once the limit is reached
+    fn push(&mut self, bytes: &[u8]) -> bool {
+        let available = self.limit - self.bytes.len();
+        if bytes.len() > available {
+            self.bytes.extend_from_slice(&bytes[..available]);
+            self.truncated = true;
+            return false;
+        }
+        self.bytes.extend_from_slice(bytes);
+        true
+    }
+}
+
+/// Builds the error of a failed response, with its body decoded lossily since
+/// proxies may return bodies which are not UTF-8
+fn response_error(status: reqwest::StatusCode, body: ErrorBody, retries_exhausted: bool) -> Error {
+    let mut error = format!("Response error: {}", status);
+    if retries_exhausted {
+        error.push_str(" (retries exhausted)");
+    }
+    let text = String::from_utf8_lossy(&body.bytes);
+    if !text.trim().is_empty() {
+        error.push_str(": ");
+        error.push_str(text.trim());
+    }
+    if body.truncated {
+        error.push_str(&format!(" (body truncated to {} bytes)", body.limit));
+    }
+    anyhow!(error)
+}
+
+/// Parses a NDJSON line as a message, none for an empty line
+fn parse_ndjson_line(line: std::io::Result<String>) -> Option<Result<RocketChatMessage, Error>> {
+    let line = match line {
+        Result::Ok(line) => line,
+        Err(e) => return Some(Err(anyhow!("Read error: {}", e))),
+    };
+    if line.trim().is_empty() {
+        return None;
+    }
+    Some(serde_json::from_str(&line).map_err(|e| anyhow!("Parse error: {}", e)))
+}
+
+/// Escapes markdown characters of a text, so it is displayed as is by rocket chat
+///
+/// Mentions (`@username`, `@all`, `@here`) are kept as is, so they still notify
+/// users, see `mention_user`.
+///
+/// ```
+/// assert_eq!(escape_markdown("*bold* [link](url)"), "\\*bold\\* \\[link\\]\\(url\\)");
+/// assert_eq!(escape_markdown("cc @on_call"), "cc @on_call");
+/// ```
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut in_mention = false;
+    for c in text.chars() {
+        in_mention = match c {
+            '@' => true,
+            _ => in_mention && is_username_char(c),
+        };
+        if !in_mention
+            && matches!(
+                c,
+                '\\' | '*' | '_' | '~' | '`' | '[' | ']' | '(' | ')' | '>' | '#' | '|'
+            )
+        {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Returns true if a char is allowed in rocket chat usernames
+fn is_username_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')
+}
+
+/// Removes markdown of a text, keeping the text of links followed by their url
+fn strip_markdown(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => stripped.extend(chars.next()),
+            // Underscores inside words, like in snake_case, are not emphasis
+            '_' if stripped.chars().last().is_some_and(char::is_alphanumeric)
+                && chars.peek().is_some_and(|c| c.is_alphanumeric()) =>
+            {
+                stripped.push('_')
+            }
+            '*' | '_' | '~' | '`' => {}
+            '[' => {
+                let label: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                if chars.peek() == Some(&'(') {
+                    chars.next();
+                    let url: String = chars.by_ref().take_while(|c| *c != ')').collect();
+                    stripped.push_str(&format!("{} ({})", strip_markdown(&label), url));
+                } else {
+                    stripped.push('[');
+                    stripped.push_str(&strip_markdown(&label));
+                    stripped.push(']');
+                }
+            }
+            c => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+/// Normalizes a channel name, prefixing a plain name with `#`
+///
+/// Names already starting with `#` (channel) or `@` (user) are kept. Names must
+/// match the default name validation of rocket chat: letters, digits, `.`, `_`
+/// and `-`.
+///
+/// ```
+/// assert_eq!(normalize_channel("general")?, "#general");
+/// assert_eq!(normalize_channel("@alice")?, "@alice");
+/// assert!(normalize_channel("#").is_err());
+/// ```
+pub fn normalize_channel(input: &str) -> Result<String, Error> {
+    normalize_channel_with(input, '#')
+}
+
+/// Normalizes a channel name, prefixing a plain name with a default prefix
+/// (`#` or `@`), see `normalize_channel`
+///
+/// ```
+/// assert_eq!(normalize_channel_with("alice", '@')?, "@alice");
+/// ```
+pub fn normalize_channel_with(input: &str, default_prefix: char) -> Result<String, Error> {
+    if !matches!(default_prefix, '#' | '@') {
+        bail!("Invalid channel prefix: {}", default_prefix);
+    }
+    let input = input.trim();
+    let (prefix, name) = match input.chars().next() {
+        Some(prefix @ ('#' | '@')) => (prefix, &input[1..]),
+        _ => (default_prefix, input),
+    };
+    if name.is_empty() {
+        bail!("Invalid channel: empty name");
+    }
+    if let Some(c) = name.chars().find(|c| !is_username_char(*c)) {
+        bail!("Invalid channel: {:?} is not allowed in {}", c, input);
+    }
+    Ok(format!("{}{}", prefix, name))
+}
+
+/// Returns the mention of a user or a group, notifying them
+///
+/// A leading `@` is optional. Mentions must not be escaped with a markdown
+/// escape other than `escape_markdown`, which keeps them as is.
+///
+/// ```
+/// let text = format!("{} the deploy failed", mention_user("alice"));
+/// ```
+pub fn mention_user(username: &str) -> String {
+    format!("@{}", username.trim().trim_start_matches('@'))
+}
+
+/// Returns the mention of all the members of the channel
+///
+/// ```
+/// let text = format!("{} maintenance in 10 minutes", mention_all());
+/// ```
+pub fn mention_all() -> String {
+    mention_user("all")
+}
+
+/// Returns the mention of the active members of the channel
+///
+/// ```
+/// let text = format!("{} the api is down", mention_here());
+/// ```
+pub fn mention_here() -> String {
+    mention_user("here")
+}
+
+/// Returns true if a request error is worth retrying
+///
+/// Timeouts are not retried since the message may have been delivered, unless
+/// the connection itself timed out.
+fn is_retryable_error(error: &reqwest::Error) -> bool {
+    !error.is_timeout() || error.is_connect()
+}
+
+/// Returns true if a response status is worth retrying
+fn is_retryable(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Returns true if a color is an hex color (`#rgb` or `#rrggbb`) or a color name
+fn is_valid_color(color: &str) -> bool {
+    check_color(color).is_ok()
+}
+
+/// Checks a color is an hex color (`#rgb` or `#rrggbb`) or a color name
+fn check_color(color: &str) -> Result<(), Error> {
+    let valid = match color.strip_prefix('#') {
+        Some(hex) if !hex.chars().all(|c| c.is_ascii_hexdigit()) => false,
+        Some(hex) if matches!(hex.len(), 4 | 8) => {
+            bail!(
+                "Invalid color: {} has an alpha channel, use #rgb or #rrggbb",
+                color
+            )
+        }
+        Some(hex) => matches!(hex.len(), 3 | 6),
+        None => !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic()),
+    };
+    if !valid {
+        bail!("Invalid color: {}", color);
+    }
+    Ok(())
+}
+
+/// Removes ANSI escape sequences and control characters except newlines and tabs
+fn sanitize(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI sequence, ended by a byte in range 0x40..=0x7e
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC sequence, ended by BEL or ST (ESC \)
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next() == Some('\\')) {
+                            break;
+                        }
+                    }
+                }
+                // Other sequences are made of a single character
+                _ => {}
+            },
+            '\n' | '\t' => sanitized.push(c),
+            c if c.is_control() => {}
+            c => sanitized.push(c),
+        }
+    }
+    sanitized
+}
+
+/// Formats a time in UTC, see `RocketChat::set_append_timestamp`
+fn format_timestamp(fmt: &str, time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    let mut formatted = String::with_capacity(fmt.len() + 8);
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => formatted.push_str(&format!("{:04}", year)),
+            Some('m') => formatted.push_str(&format!("{:02}", month)),
+            Some('d') => formatted.push_str(&format!("{:02}", day)),
+            Some('H') => formatted.push_str(&format!("{:02}", secs / 3600)),
+            Some('M') => formatted.push_str(&format!("{:02}", secs % 3600 / 60)),
+            Some('S') => formatted.push_str(&format!("{:02}", secs % 60)),
+            Some('%') => formatted.push('%'),
+            Some(other) => {
+                formatted.push('%');
+                formatted.push(other);
+            }
+            None => formatted.push('%'),
+        }
+    }
+    formatted
+}
+
+/// Splits a text in pages of at most `max_chars` chars, preferably on paragraph
+/// breaks, then on line breaks
+fn paginate(text: &str, max_chars: usize) -> Result<Vec<&str>, Error> {
+    if max_chars == 0 {
+        bail!("Invalid page size: 0");
+    }
+    let mut pages = Vec::new();
+    let mut rest = text;
+
+    while let Some((limit, _)) = rest.char_indices().nth(max_chars) {
+        let head = &rest[..limit];
+        let (end, next) = match (head.rfind("\n\n"), head.rfind('\n')) {
+            (Some(i), _) if i > 0 => (i, i + 2),
+            (_, Some(i)) if i > 0 => (i, i + 1),
+            _ => (limit, limit),
+        };
+        pages.push(&rest[..end]);
+        rest = &rest[next..];
+    }
+    if !rest.is_empty() {
+        pages.push(rest);
+    }
+    Ok(pages)
+}
+
+/// Wraps a text in a code block fitting in a message
+fn code_block(lang: &str, body: &str) -> String {
+    let fences = format!("```{}\n\n```", lang);
+    let max_chars = MAX_MESSAGE_LENGTH.saturating_sub(fences.chars().count());
+
+    format!(
+        "```{}\n{}\n```",
+        lang,
+        truncate(&escape_fences(body), max_chars)
+    )
+}
+
+/// Splits triple backticks of a text with a zero width space, so they don't end
+/// a code block
+fn escape_fences(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut backticks = 0;
+    for c in text.chars() {
+        if c == '`' {
+            if backticks == 2 {
+                escaped.push('\u{200b}');
+                backticks = 0;
+            }
+            backticks += 1;
+        } else {
+            backticks = 0;
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Truncate a text to a number of chars, ending with an ellipsis if truncated
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        None => text.to_string(),
+        Some(_) if max_chars == 0 => String::new(),
+        Some(_) => {
+            let end = text.char_indices().nth(max_chars - 1).map_or(0, |(i, _)| i);
+            format!("{}…", &text[..end])
+        }
+    }
+}
+
+/// A structure representing a rocket chat field for attachments
+///
+/// Fields are serialized in the order they are declared.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct Field {
+    /// Size of field (default false by rocket chat)
+    pub short: Option<bool>,
+    /// Title of field
+    pub title: String,
+    /// Value of field
+    pub value: String,
+}
+
+impl Field {
+    /// Create new field
+    ///
+    /// ```
+    /// let field = Field::new();
+    /// ```
+    pub fn new() -> Self {
+        Field::default()
+    }
+
+    /// Change the title of the field
+    ///
+    /// ```
+    /// let field = Field::new().set_title("Title");
+    /// ```
+    pub fn set_title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Change the value of the field
+    ///
+    /// ```
+    /// let field = Field::new().set_value("Value");
+    /// ```
+    pub fn set_value<S: Into<String>>(mut self, value: S) -> Self {
+        self.value = value.into();
+        self
+    }
+
+    /// Change the value of the field, truncated to a number of chars
+    ///
+    /// A truncated value ends with an ellipsis, counted in the number of chars.
+    ///
+    /// ```
+    /// let field = Field::new().set_value_truncated(commit_message, 200);
+    /// ```
+    pub fn set_value_truncated<S: AsRef<str>>(mut self, value: S, max_chars: usize) -> Self {
+        self.value = truncate(value.as_ref(), max_chars);
+        self
+    }
+
+    /// Change the short of the field
+    ///
+    /// ```
+    /// let field = Field::new().set_short(true);
+    /// ```
+    pub fn set_short(mut self, value: bool) -> Self {
+        self.short = Some(value);
+        self
+    }
+}
+
+impl From<(&str, &str)> for Field {
+    /// Create a field from a title and a value
+    ///
+    /// ```
+    /// let field: Field = ("CPU", "93%").into();
+    /// ```
+    fn from((title, value): (&str, &str)) -> Self {
+        Field::new().set_title(title).set_value(value)
+    }
+}
+
+impl From<(&str, &str, bool)> for Field {
+    /// Create a field from a title, a value and whether it is short
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_fields(vec![
+    ///     ("CPU", "93%", true).into(),
+    ///     ("Memory", "41%", true).into(),
+    /// ]);
+    /// ```
+    fn from((title, value, short): (&str, &str, bool)) -> Self {
+        Field::new()
+            .set_title(title)
+            .set_value(value)
+            .set_short(short)
+    }
+}
+
+/// A structure representing the dimensions of an attachment image
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageDimensions {
+    /// Width of image in pixels
+    pub width: u32,
+    /// Height of image in pixels
+    pub height: u32,
+}
+
+/// A structure representing build metadata of a service, displayed on attachments
+#[derive(Debug, Clone, Default)]
+pub struct BuildInfo {
+    /// Version of the build
+    pub version: Option<String>,
+    /// Commit of the build
+    pub commit: Option<String>,
+    /// Branch of the build
+    pub branch: Option<String>,
+    /// Environment of the build
+    pub env: Option<String>,
+}
+
+impl BuildInfo {
+    /// Create new build info
+    ///
+    /// ```
+    /// let info = BuildInfo::new();
+    /// ```
+    pub fn new() -> Self {
+        BuildInfo::default()
+    }
+
+    /// Change the version of the build
+    ///
+    /// ```
+    /// let info = BuildInfo::new().set_version("1.2.0");
+    /// ```
+    pub fn set_version<S: Into<String>>(mut self, version: S) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Change the commit of the build
+    ///
+    /// ```
+    /// let info = BuildInfo::new().set_commit("abc1234");
+    /// ```
+    pub fn set_commit<S: Into<String>>(mut self, commit: S) -> Self {
+        self.commit = Some(commit.into());
+        self
+    }
+
+    /// Change the branch of the build
+    ///
+    /// ```
+    /// let info = BuildInfo::new().set_branch("main");
+    /// ```
+    pub fn set_branch<S: Into<String>>(mut self, branch: S) -> Self {
+        self.branch = Some(branch.into());
+        self
+    }
+
+    /// Change the environment of the build
+    ///
+    /// ```
+    /// let info = BuildInfo::new().set_env("production");
+    /// ```
+    pub fn set_env<S: Into<String>>(mut self, env: S) -> Self {
+        self.env = Some(env.into());
+        self
+    }
+
+    /// Returns the footer for the build, like `version 1.2.0 | commit abc1234`
+    ///
+    /// ```
+    /// let footer = BuildInfo::new().set_version("1.2.0").footer();
+    /// ```
+    pub fn footer(&self) -> String {
+        self.entries()
+            .map(|(title, value)| format!("{} {}", title.to_lowercase(), value))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    /// Returns the title and value of each metadata set
+    fn entries(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            ("Version", &self.version),
+            ("Commit", &self.commit),
+            ("Branch", &self.branch),
+            ("Env", &self.env),
+        ]
+        .into_iter()
+        .filter_map(|(title, value)| value.as_deref().map(|value| (title, value)))
+    }
+}
+
+/// Color of attachments for a good state
+const COLOR_GOOD: &str = "#2eb886";
+/// Color of attachments for a warning state
+const COLOR_WARNING: &str = "#daa038";
+/// Color of attachments for a danger state
+const COLOR_DANGER: &str = "#a30200";
+
+/// Default keywords of `RocketChatAttachment::auto_color_from_keywords`, with
+/// their color, by priority
+pub const DEFAULT_COLOR_KEYWORDS: &[(&str, &str)] = &[
+    ("error", COLOR_DANGER),
+    ("fail", COLOR_DANGER),
+    ("warn", COLOR_WARNING),
+    ("ok", COLOR_GOOD),
+];
+
+/// Severity of an attachment, displayed as its color
+///
+/// The colors can be changed per client with `RocketChat::set_severity_colors`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    /// Blue by default
+    Info,
+    /// Green by default
+    Success,
+    /// Orange by default
+    Warning,
+    /// Red by default
+    Danger,
+}
+
+impl Severity {
+    /// Returns the default color of the severity
+    pub fn default_color(self) -> &'static str {
+        match self {
+            Severity::Info => "#439fe0",
+            Severity::Success => COLOR_GOOD,
+            Severity::Warning => COLOR_WARNING,
+            Severity::Danger => COLOR_DANGER,
+        }
+    }
+}
+
+/// A structure representing a rocket chat attachment
+///
+/// Fields are serialized in the order they are declared, followed by the
+/// extra keys sorted by name.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct RocketChatAttachment {
+    /// Title of attachment
+    pub title: Option<String>,
+    /// Link for title of attachment
+    pub title_link: Option<String>,
+    /// Color on border left of attachment
+    pub color: Option<String>,
+    /// Author name of attachment
+    pub author_name: Option<String>,
+    /// Author icon of attachment (displayed only if author name is defined)
+    pub author_icon: Option<String>,
+    /// Text of attachment
+    pub text: Option<String>,
+    /// Short description of attachment, displayed apart from text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Image of attachment
+    pub image_url: Option<String>,
+    /// Dimensions of image, to reserve its space before loading
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_dimensions: Option<ImageDimensions>,
+    /// Fields of attachment
+    pub fields: Vec<Field>,
+    /// Footer of attachment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer: Option<String>,
+    /// Names of the parts of attachment rendered as markdown, like `text`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mrkdwn_in: Vec<String>,
+    /// Extra keys of attachment, not modeled by this crate
+    #[serde(flatten)]
+    pub extras: serde_json::Map<String, serde_json::Value>,
+    /// Severity of attachment, giving its color when sent if none is set
+    #[serde(skip)]
+    pub severity: Option<Severity>,
+}
+
+impl RocketChatAttachment {
+    /// Create new attachment
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new();
+    /// ```
+    pub fn new() -> Self {
+        RocketChatAttachment::default()
+    }
+
+    /// Create new attachment with a title and fields from `(title, value, short)`
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::with_fields(
+    ///     "Server status",
+    ///     vec![("CPU", "93%", true), ("Memory", "41%", true), ("Uptime", "12 days", false)],
+    /// );
+    /// ```
+    pub fn with_fields<S: Into<String>>(title: S, fields: Vec<(&str, &str, bool)>) -> Self {
+        RocketChatAttachment::new()
+            .set_title(title)
+            .set_fields(fields.into_iter().map(Field::from).collect())
+    }
+
+    /// Create new attachment describing an error
+    ///
+    /// The error is the title, in danger color, and each error of its source
+    /// chain is added as a "Caused by" field.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::from_error(&err);
+    /// ```
+    pub fn from_error<E: std::error::Error + ?Sized>(err: &E) -> Self {
+        let mut fields = Vec::new();
+        let mut source = err.source();
+        while let Some(cause) = source {
+            fields.push(
+                Field::new()
+                    .set_title("Caused by")
+                    .set_value(cause.to_string()),
+            );
+            source = cause.source();
+        }
+
+        RocketChatAttachment::new()
+            .set_title(err.to_string())
+            .set_color(COLOR_DANGER)
+            .set_fields(fields)
+    }
+
+    /// Create new attachment describing an anyhow error, with its backtrace
+    ///
+    /// Same as `from_error`, with the backtrace in a code block as text when it
+    /// was captured (see `RUST_BACKTRACE` and `RUST_LIB_BACKTRACE`).
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::from_error_with_backtrace(&err);
+    /// ```
+    #[cfg(feature = "backtrace")]
+    pub fn from_error_with_backtrace(err: &Error) -> Self {
+        let attachment = Self::from_error(AsRef::<dyn std::error::Error>::as_ref(err));
+
+        let backtrace = err.backtrace();
+        match backtrace.status() {
+            std::backtrace::BacktraceStatus::Captured => {
+                attachment.set_text(format!("```\n{}\n```", backtrace))
+            }
+            _ => attachment,
+        }
+    }
+
+    /// Change the title of the attachment
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_title("Title");
+    /// ```
+    pub fn set_title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Change the title link of attachment
+    ///
+    /// An empty link removes the link, the title is then displayed as plain text.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_title_link("https://google.fr");
+    /// ```
+    pub fn set_title_link<S: Into<String>>(mut self, title_link: S) -> Self {
+        let title_link = title_link.into();
+        self.title_link = (!title_link.is_empty()).then_some(title_link);
+        self
+    }
+
+    /// Change the title link of attachment, checking it is an http(s) url
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().try_set_title_link("https://google.fr")?;
+    /// ```
+    pub fn try_set_title_link<S: Into<String>>(self, title_link: S) -> Result<Self, Error> {
+        let title_link = title_link.into();
+
+        let parsed =
+            reqwest::Url::parse(&title_link).map_err(|e| anyhow!("Invalid title link: {}", e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            bail!("Invalid title link: expected an http(s) url");
+        }
+        Ok(self.set_title_link(title_link))
+    }
+
+    /// Change the color of attachment
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_color("#c97149");
+    /// ```
+    pub fn set_color<S: Into<String>>(mut self, color: S) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    /// Change the severity of the attachment
+    ///
+    /// When the attachment has no color, its color is the color of the severity
+    /// in the palette of the client sending it (see `RocketChat::set_severity_colors`),
+    /// or the default color of the severity.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_severity(Severity::Warning);
+    /// ```
+    pub fn set_severity(mut self, severity: Severity) -> Self {
+        self.severity = Some(severity);
+        self
+    }
+
+    /// Change the color of the attachment, checking it is valid
+    ///
+    /// Rocket chat supports `#rgb` and `#rrggbb` hex colors and color names,
+    /// hex colors with an alpha channel (`#rrggbbaa`) are not displayed.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().try_set_color("#c97149")?;
+    ///
+    /// assert!(RocketChatAttachment::new().try_set_color("#c97149ff").is_err());
+    /// ```
+    pub fn try_set_color<S: Into<String>>(self, color: S) -> Result<Self, Error> {
+        let color = color.into();
+        check_color(&color)?;
+        Ok(self.set_color(color))
+    }
+
+    /// Change the color of the attachment by comparing a value to thresholds,
+    /// for metrics where higher is worse
+    ///
+    /// The color is good (green) under `warn`, warning (orange) under `crit`
+    /// and danger (red) otherwise.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().color_by_threshold(cpu_usage, 70.0, 90.0);
+    /// ```
+    pub fn color_by_threshold(self, value: f64, warn: f64, crit: f64) -> Self {
+        let color = if value < warn {
+            COLOR_GOOD
+        } else if value < crit {
+            COLOR_WARNING
+        } else {
+            COLOR_DANGER
+        };
+        self.set_color(color)
+    }
+
+    /// Change the color of the attachment by comparing a value to thresholds,
+    /// for metrics where lower is worse
+    ///
+    /// The color is good (green) above `warn`, warning (orange) above `crit`
+    /// and danger (red) otherwise.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().color_by_threshold_inverted(free_disk, 20.0, 5.0);
+    /// ```
+    pub fn color_by_threshold_inverted(self, value: f64, warn: f64, crit: f64) -> Self {
+        let color = if value > warn {
+            COLOR_GOOD
+        } else if value > crit {
+            COLOR_WARNING
+        } else {
+            COLOR_DANGER
+        };
+        self.set_color(color)
+    }
+
+    /// Change the color of the attachment from keywords of its title & text,
+    /// with the default keywords (see `DEFAULT_COLOR_KEYWORDS`)
+    ///
+    /// This is a best-effort heuristic, for quick alerts without a severity: a
+    /// keyword matches the start of a word, case insensitive, so `fail` matches
+    /// "Failed" but `ok` doesn't match "token". The first matching keyword of the
+    /// list gives the color, and the color is unchanged if none matches.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new()
+    ///     .set_text("Backup failed")
+    ///     .auto_color_from_keywords();
+    /// ```
+    pub fn auto_color_from_keywords(self) -> Self {
+        self.auto_color_from_keywords_with(DEFAULT_COLOR_KEYWORDS)
+    }
+
+    /// Change the color of the attachment from keywords of its title & text,
+    /// with custom `(keyword, color)` pairs, see `auto_color_from_keywords`
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new()
+    ///     .set_text("Deploy rolled back")
+    ///     .auto_color_from_keywords_with(&[("rolled back", "#daa038"), ("deployed", "#2eb886")]);
+    /// ```
+    pub fn auto_color_from_keywords_with(self, keywords: &[(&str, &str)]) -> Self {
+        let content = [self.title.as_deref(), self.text.as_deref()]
+            .iter()
+            .flatten()
+            .map(|part| part.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let color = keywords.iter().find_map(|(keyword, color)| {
+            let keyword = keyword.to_lowercase();
+            content
+                .match_indices(&keyword)
+                .any(|(i, _)| {
+                    content[..i]
+                        .chars()
+                        .next_back()
+                        .is_none_or(|c| !c.is_alphanumeric())
+                })
+                .then_some(*color)
+        });
+        match color {
+            Some(color) => self.set_color(color),
+            None => self,
+        }
+    }
+
+    /// Change the author name & icon of attachment
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_author("Author Name", Some("ICON_URL"));
+    /// ```
+    pub fn set_author<S: Into<String>>(mut self, name: S, icon: Option<S>) -> Self {
+        self.author_name = Some(name.into());
+        if let Some(icon) = icon {
+            self.author_icon = Some(icon.into());
+        }
+        self
+    }
+
+    /// Change the content of attachment
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_text("Text");
+    /// ```
+    pub fn set_text<S: Into<String>>(mut self, text: S) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    /// Change the content of attachment, removing terminal escape sequences and
+    /// control characters
+    ///
+    /// ANSI escape sequences (colors, cursor moves, terminal titles) are removed,
+    /// as well as all control characters except newlines and tabs. Carriage
+    /// returns are removed too.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_text_sanitized("\x1b[31mError\x1b[0m");
+    /// ```
+    pub fn set_text_sanitized<S: AsRef<str>>(self, text: S) -> Self {
+        self.set_text(sanitize(text.as_ref()))
+    }
+
+    /// Change the text of the attachment to a code block, rendered as markdown
+    ///
+    /// Triple backticks inside the body are split with a zero width space, so
+    /// they don't end the block.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_code_text("json", &payload);
+    /// ```
+    pub fn set_code_text(mut self, lang: &str, body: &str) -> Self {
+        self.text = Some(format!("```{}\n{}\n```", lang, escape_fences(body)));
+        self.add_mrkdwn_in("text");
+        self
+    }
+
+    /// Render the values of all fields of the attachment as markdown
+    ///
+    /// Rocket chat renders fields as markdown when `mrkdwn_in` contains
+    /// `fields`, so it is all or nothing per attachment: put fields that must
+    /// not be rendered in another attachment.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new()
+    ///     .set_fields(vec![("Status", "*down*", true).into()])
+    ///     .enable_field_markdown();
+    /// ```
+    pub fn enable_field_markdown(mut self) -> Self {
+        self.add_mrkdwn_in("fields");
+        self
+    }
+
+    /// Adds a part of the attachment rendered as markdown, if not already there
+    fn add_mrkdwn_in(&mut self, part: &str) {
+        if !self.mrkdwn_in.iter().any(|name| name == part) {
+            self.mrkdwn_in.push(part.to_string());
+        }
+    }
+
+    /// Change the description of attachment
+    ///
+    /// The description is a short line rendered separately from the text,
+    /// both can be set on the same attachment.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new()
+    ///     .set_description("Short description")
+    ///     .set_text("Longer text");
+    /// ```
+    pub fn set_description<S: Into<String>>(mut self, text: S) -> Self {
+        self.description = Some(text.into());
+        self
+    }
+
+    /// Change the image of attachment
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_image("IMAGE_URL");
+    /// ```
+    pub fn set_image<S: Into<String>>(mut self, url: S) -> Self {
+        self.image_url = Some(url.into());
+        self
+    }
+
+    /// Change the dimensions of the image of attachment
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new()
+    ///     .set_image("IMAGE_URL")
+    ///     .set_image_dimensions(1280, 720);
+    /// ```
+    pub fn set_image_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.image_dimensions = Some(ImageDimensions { width, height });
+        self
+    }
+
+    /// Change the footer of attachment
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_footer("Footer");
+    /// ```
+    pub fn set_footer<S: Into<String>>(mut self, footer: S) -> Self {
+        self.footer = Some(footer.into());
+        self
+    }
+
+    /// Change the footer of attachment and add fields from build metadata
+    ///
+    /// The footer is replaced by `BuildInfo::footer`, and a short field is
+    /// appended for each metadata set. The footer can still be changed afterwards
+    /// with `set_footer`.
+    ///
+    /// ```
+    /// let info = BuildInfo::new().set_version("1.2.0").set_commit("abc1234");
+    /// let attachment = RocketChatAttachment::new().set_build_info(&info);
+    /// ```
+    pub fn set_build_info(mut self, info: &BuildInfo) -> Self {
+        self.footer = Some(info.footer());
+        self.fields.extend(info.entries().map(|(title, value)| {
+            Field::new()
+                .set_title(title)
+                .set_value(value)
+                .set_short(true)
+        }));
+        self
+    }
+
+    /// Check the attachment for fields ignored by rocket chat
+    ///
+    /// The author icon is only displayed with an author name, the title link
+    /// only with a title, and the color must be valid.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_title_link("https://google.fr");
+    ///
+    /// assert!(attachment.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.author_icon.is_some() && self.author_name.is_none() {
+            bail!("Author icon is ignored without author name");
+        }
+        if self.title_link.is_some() && self.title.is_none() {
+            bail!("Title link is ignored without title");
+        }
+        if let Some(color) = &self.color {
+            check_color(color)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the lints of the attachment, see `RocketChatMessage::lint`
+    fn lint_into(&self, index: usize, lints: &mut Vec<Lint>) {
+        let mut lint = |message: &str| lints.push(Lint::attachment(index, message));
+
+        if self.title.is_none()
+            && self.text.is_none()
+            && self.description.is_none()
+            && self.author_name.is_none()
+            && self.image_url.is_none()
+            && self.footer.is_none()
+            && self.fields.is_empty()
+        {
+            lint("empty attachment");
+        }
+        if self.author_icon.is_some() && self.author_name.is_none() {
+            lint("author_icon set without author_name");
+        }
+        if self.title_link.is_some() && self.title.is_none() {
+            lint("title_link set without title");
+        }
+        if self.image_dimensions.is_some() && self.image_url.is_none() {
+            lint("image_dimensions set without image_url");
+        }
+        if let Some(color) = &self.color {
+            if let Err(e) = check_color(color) {
+                lint(&e.to_string());
+            }
+        }
+        if self
+            .fields
+            .iter()
+            .any(|field| field.title.is_empty() && field.value.is_empty())
+        {
+            lint("empty field");
+        }
+    }
+
+    /// Change the fields of attachment
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_fields(vec![Field::new()
+    ///     .set_title("Field title")
+    ///     .set_value("Field value")
+    ///     .set_short(true)]);
+    /// ```
+    pub fn set_fields(mut self, fields: Vec<Field>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Split the fields of attachment across several attachments, of at most
+    /// `max_per_attachment` fields each
+    ///
+    /// Every chunk shares the title, color and severity of the attachment, and
+    /// how fields are rendered (`mrkdwn_in`). The other properties, like text or
+    /// image, are kept only on the first chunk. An attachment without fields is
+    /// returned as is.
+    ///
+    /// ```
+    /// let attachments = RocketChatAttachment::new()
+    ///     .set_title("Metrics")
+    ///     .set_fields(fields)
+    ///     .split_fields(20);
+    ///
+    /// let msg = RocketChatMessage::new().set_attachments(attachments);
+    /// ```
+    pub fn split_fields(mut self, max_per_attachment: usize) -> Vec<RocketChatAttachment> {
+        let max_per_attachment = max_per_attachment.max(1);
+        if self.fields.len() <= max_per_attachment {
+            return vec![self];
+        }
+
+        let mut fields = std::mem::take(&mut self.fields).into_iter();
+        let shared = RocketChatAttachment {
+            title: self.title.clone(),
+            color: self.color.clone(),
+            severity: self.severity,
+            mrkdwn_in: self.mrkdwn_in.clone(),
+            ..Default::default()
+        };
+
+        self.fields = fields.by_ref().take(max_per_attachment).collect();
+        let mut attachments = vec![self];
+        loop {
+            let chunk: Vec<Field> = fields.by_ref().take(max_per_attachment).collect();
+            if chunk.is_empty() {
+                break;
+            }
+            attachments.push(shared.clone().set_fields(chunk));
+        }
+        attachments
+    }
+
+    /// Start adding fields to the attachment one by one
+    ///
+    /// Fields are pushed directly to the attachment, without an intermediate
+    /// vector, and are appended after the existing fields.
+    ///
+    /// ```
+    /// let mut builder = RocketChatAttachment::new().fields_builder().reserve(rows.len());
+    /// for row in rows {
+    ///     builder.push(Field::new().set_title(row.name).set_value(row.value));
+    /// }
+    /// let attachment = builder.finish();
+    /// ```
+    pub fn fields_builder(self) -> FieldsBuilder {
+        FieldsBuilder { attachment: self }
+    }
+
+    /// Add an extra key to the attachment, for rocket chat features not modeled
+    /// by this crate
+    ///
+    /// Extra keys are serialized after the other keys of the attachment. A key
+    /// colliding with another key, like `title`, is serialized twice, and which
+    /// value rocket chat uses is unspecified.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_extra("collapsed", serde_json::json!(true));
+    /// ```
+    pub fn set_extra(mut self, key: &str, value: serde_json::Value) -> Self {
+        self.extras.insert(key.to_string(), value);
+        self
+    }
+
+    /// Change the fields of attachment, all short or not short
+    ///
+    /// Fields with short already set keep their own value.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_fields_short(
+    ///     vec![Field::new().set_title("CPU"), Field::new().set_title("Memory")],
+    ///     true,
+    /// );
+    /// ```
+    pub fn set_fields_short(mut self, fields: Vec<Field>, short: bool) -> Self {
+        self.fields = fields
+            .into_iter()
+            .map(|mut field| {
+                field.short = field.short.or(Some(short));
+                field
+            })
+            .collect();
+        self
+    }
+
+    /// Change the fields of attachment from rows of fields
+    ///
+    /// Rocket chat displays consecutive short fields two per row, and a field
+    /// which is not short on its own row. Fields of each row are paired as short
+    /// fields, and a field left alone on its row is set as not short, so the next
+    /// row always starts on a new line.
+    ///
+    /// ```
+    /// let attachment = RocketChatAttachment::new().set_fields_grid(vec![
+    ///     vec![Field::new().set_title("CPU"), Field::new().set_title("Memory")],
+    ///     vec![Field::new().set_title("Disk")],
+    /// ]);
+    /// ```
+    pub fn set_fields_grid(mut self, rows: Vec<Vec<Field>>) -> Self {
+        self.fields = rows
+            .into_iter()
+            .flat_map(|row| {
+                let len = row.len();
+                row.into_iter()
+                    .enumerate()
+                    .map(move |(i, field)| field.set_short(len % 2 == 0 || i + 1 < len))
+            })
+            .collect();
+        self
+    }
+}
+
+/// A structure adding fields to an attachment incrementally, see
+/// `RocketChatAttachment::fields_builder`
+pub struct FieldsBuilder {
+    /// Attachment receiving the fields
+    attachment: RocketChatAttachment,
+}
+
+impl FieldsBuilder {
+    /// Reserve capacity for at least `additional` more fields
+    pub fn reserve(mut self, additional: usize) -> Self {
+        self.attachment.fields.reserve(additional);
+        self
+    }
+
+    /// Add a field after the previous ones
+    pub fn push(&mut self, field: Field) -> &mut Self {
+        self.attachment.fields.push(field);
+        self
+    }
+
+    /// Returns the attachment with its fields
+    pub fn finish(self) -> RocketChatAttachment {
+        self.attachment
+    }
+}
+
+impl Extend<Field> for FieldsBuilder {
+    fn extend<I: IntoIterator<Item = Field>>(&mut self, fields: I) {
+        self.attachment.fields.extend(fields);
+    }
+}
+
+/// Body sent to rocket chat, serialized as the channel or room id followed by
+/// the fields of the message in declaration order
+#[derive(Serialize)]
+struct RocketChatMessagePayload<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel: Option<&'a str>,
+    #[serde(rename = "roomId", skip_serializing_if = "Option::is_none")]
+    room_id: Option<&'a str>,
+    #[serde(flatten)]
+    message: &'a RocketChatMessage,
+}
+
+/// A structure representing a rocket chat message
+///
+/// Fields are serialized in the order they are declared, after the channel
+/// or room id of the client, so the JSON body of a message is stable. Keys of
+/// custom fields and extras are sorted by name.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+// #[serde(rename_all = "camelCase")]
+pub struct RocketChatMessage {
+    /// Text on top of attachments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Attachments linked to message
+    pub attachments: Vec<RocketChatAttachment>,
+    /// Parse urls of message to display previews (default true by rocket chat)
+    #[serde(rename = "parseUrls", skip_serializing_if = "Option::is_none")]
+    pub parse_urls: Option<bool>,
+    /// Icon url of the bot posting message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_url: Option<String>,
+    /// Avatar url of the user posting message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+    /// Emoji displayed as avatar of the user posting message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    /// Id of the parent message, to reply in its thread
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tmid: Option<String>,
+    /// Also show the thread reply in the channel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tshow: Option<bool>,
+    /// Group message with previous messages of the same user (default true by rocket chat)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groupable: Option<bool>,
+    /// Custom fields of message, configured on the rocket chat server
+    #[serde(
+        rename = "customFields",
+        skip_serializing_if = "serde_json::Map::is_empty"
+    )]
+    pub custom_fields: serde_json::Map<String, serde_json::Value>,
+    /// Don't append the default attachment of the client
+    #[serde(skip)]
+    pub skip_default_attachment: bool,
+    /// Extra keys of message, not modeled by this crate
+    #[serde(flatten)]
+    pub extras: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Keys of the body managed by this crate, which extra keys can't override
+const RESERVED_KEYS: &[&str] = &[
+    "channel",
+    "roomId",
+    "text",
+    "attachments",
+    "parseUrls",
+    "icon_url",
+    "avatar",
+    "emoji",
+    "tmid",
+    "tshow",
+    "groupable",
+    "customFields",
+];
+
+impl RocketChatMessage {
+    /// Create new message
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new();
+    /// ```
+    pub fn new() -> Self {
+        RocketChatMessage::default()
+    }
+
+    /// Change the content of message
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().set_text("Text");
     /// ```
     pub fn set_text<S: Into<String>>(mut self, text: S) -> Self {
         self.text = Some(text.into());
         self
     }
 
+    /// Change the content of message, removing terminal escape sequences and
+    /// control characters
+    ///
+    /// See `RocketChatAttachment::set_text_sanitized` for what is removed.
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().set_text_sanitized("\x1b[31mError\x1b[0m");
+    /// ```
+    pub fn set_text_sanitized<S: AsRef<str>>(self, text: S) -> Self {
+        self.set_text(sanitize(text.as_ref()))
+    }
+
     /// Change the attachments of message
     ///
     /// ```
@@ -412,4 +3897,437 @@ impl RocketChatMessage {
         self.attachments = attachments;
         self
     }
+
+    /// Change the attachments of message from an iterator
+    ///
+    /// ```
+    /// let titles = vec!["Title1", "Title2"];
+    /// let message = RocketChatMessage::new()
+    ///     .attachments_from(titles.into_iter().map(|t| RocketChatAttachment::new().set_title(t)));
+    /// ```
+    pub fn attachments_from<I: IntoIterator<Item = RocketChatAttachment>>(
+        mut self,
+        iter: I,
+    ) -> Self {
+        self.attachments = iter.into_iter().collect();
+        self
+    }
+
+    /// Add an attachment built from a value
+    ///
+    /// ```
+    /// let event = DeployEvent { version: "1.0.0".to_string() };
+    /// let message = RocketChatMessage::new().add_attachment(&event);
+    /// ```
+    pub fn add_attachment<T: ToRocketChatAttachment + ?Sized>(mut self, value: &T) -> Self {
+        self.attachments.push(value.to_attachment());
+        self
+    }
+
+    /// Remove duplicate attachments of message
+    ///
+    /// The first occurrence of each attachment is kept, in its original order.
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new()
+    ///     .set_attachments(vec![attachment.clone(), other, attachment])
+    ///     .dedup_attachments();
+    /// ```
+    pub fn dedup_attachments(mut self) -> Self {
+        let mut attachments: Vec<RocketChatAttachment> = Vec::with_capacity(self.attachments.len());
+        for attachment in self.attachments {
+            if !attachments.contains(&attachment) {
+                attachments.push(attachment);
+            }
+        }
+        self.attachments = attachments;
+        self
+    }
+
+    /// Merge another message into this one
+    ///
+    /// Texts are joined with a newline and attachments of `other` are appended
+    /// after the ones of this message. For optional scalar fields, the value of
+    /// this message takes precedence and the value of `other` is used only if unset,
+    /// the same goes for each custom field and extra key.
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new()
+    ///     .set_text("Text")
+    ///     .merge(RocketChatMessage::new().set_text("Text2"));
+    /// ```
+    pub fn merge(self, other: RocketChatMessage) -> Self {
+        self.merge_with(other, "\n")
+    }
+
+    /// Merge another message into this one, joining texts with a separator
+    ///
+    /// See `merge` for how the other fields are merged.
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new()
+    ///     .set_text("Text")
+    ///     .merge_with(RocketChatMessage::new().set_text("Text2"), "\n\n");
+    /// ```
+    pub fn merge_with(mut self, other: RocketChatMessage, separator: &str) -> Self {
+        self.text = match (self.text, other.text) {
+            (Some(text), Some(other)) => Some(format!("{}{}{}", text, separator, other)),
+            (text, other) => text.or(other),
+        };
+        self.attachments.extend(other.attachments);
+        self.parse_urls = self.parse_urls.or(other.parse_urls);
+        self.icon_url = self.icon_url.or(other.icon_url);
+        self.avatar = self.avatar.or(other.avatar);
+        self.emoji = self.emoji.or(other.emoji);
+        self.tmid = self.tmid.or(other.tmid);
+        self.tshow = self.tshow.or(other.tshow);
+        self.groupable = self.groupable.or(other.groupable);
+        for (key, value) in other.custom_fields {
+            self.custom_fields.entry(key).or_insert(value);
+        }
+        for (key, value) in other.extras {
+            self.extras.entry(key).or_insert(value);
+        }
+        self
+    }
+
+    /// Change whether urls of message are parsed to display previews
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().set_parse_urls(false);
+    /// ```
+    pub fn set_parse_urls(mut self, parse: bool) -> Self {
+        self.parse_urls = Some(parse);
+        self
+    }
+
+    /// Change the icon url of the bot posting message
+    ///
+    /// `icon_url` is the Slack compatible field read by some integrations,
+    /// distinct from the rocket chat `avatar` and `emoji` fields which, when
+    /// set, take precedence over it.
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().set_icon_url("ICON_URL");
+    /// ```
+    pub fn set_icon_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.icon_url = Some(url.into());
+        self
+    }
+
+    /// Change the avatar url of the user posting message
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().set_avatar("AVATAR_URL");
+    /// ```
+    pub fn set_avatar<S: Into<String>>(mut self, url: S) -> Self {
+        self.avatar = Some(url.into());
+        self
+    }
+
+    /// Change the avatar url of the user posting message, checking it is an
+    /// http(s) url
+    ///
+    /// Rocket chat silently ignores an invalid avatar, like an emoji shortcode
+    /// which belongs to the emoji field.
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().try_set_avatar("https://example.com/avatar.png")?;
+    /// ```
+    pub fn try_set_avatar<S: Into<String>>(self, url: S) -> Result<Self, Error> {
+        let url = url.into();
+        if url.starts_with(':') {
+            bail!("Invalid avatar url: {} is an emoji shortcode", url);
+        }
+
+        let parsed = reqwest::Url::parse(&url).map_err(|e| anyhow!("Invalid avatar url: {}", e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            bail!("Invalid avatar url: expected an http(s) url");
+        }
+        Ok(self.set_avatar(url))
+    }
+
+    /// Change the emoji displayed as avatar of the user posting message
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().set_emoji(emoji::ROCKET);
+    /// let message = RocketChatMessage::new().set_emoji(Emoji::new(":custom_emoji:")?);
+    /// ```
+    pub fn set_emoji<E: Into<Emoji>>(mut self, emoji: E) -> Self {
+        self.emoji = Some(emoji.into().into());
+        self
+    }
+
+    /// Remove the avatar url of message
+    ///
+    /// ```
+    /// let message = message.clear_avatar();
+    /// ```
+    pub fn clear_avatar(mut self) -> Self {
+        self.avatar = None;
+        self
+    }
+
+    /// Remove the emoji of message
+    ///
+    /// ```
+    /// let message = message.clear_emoji();
+    /// ```
+    pub fn clear_emoji(mut self) -> Self {
+        self.emoji = None;
+        self
+    }
+
+    /// Change the parent message, to post message as a reply in its thread
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().set_text("Text").set_tmid("PARENT_MESSAGE_ID");
+    /// ```
+    pub fn set_tmid<S: Into<String>>(mut self, parent_message_id: S) -> Self {
+        self.tmid = Some(parent_message_id.into());
+        self
+    }
+
+    /// Change whether a thread reply is also shown in the channel
+    ///
+    /// Only used along with `set_tmid`, serialized as `tshow`.
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new()
+    ///     .set_tmid("PARENT_MESSAGE_ID")
+    ///     .set_thread_show_in_channel(true);
+    /// ```
+    pub fn set_thread_show_in_channel(mut self, show: bool) -> Self {
+        self.tshow = Some(show);
+        self
+    }
+
+    /// Change whether message is grouped with previous messages of the same user
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().set_groupable(false);
+    /// ```
+    pub fn set_groupable(mut self, groupable: bool) -> Self {
+        self.groupable = Some(groupable);
+        self
+    }
+
+    /// Add an extra key to the message, for rocket chat features not modeled
+    /// by this crate
+    ///
+    /// Keys managed by this crate, like `channel` or `text`, can't be set:
+    /// `validate` and sending the message return an error.
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().set_text("Text").set_extra("bot", serde_json::json!({ "i": "ci" }));
+    /// ```
+    pub fn set_extra(mut self, key: &str, value: serde_json::Value) -> Self {
+        self.extras.insert(key.to_string(), value);
+        self
+    }
+
+    /// Send message without the default attachment of the client, see
+    /// `RocketChat::set_default_attachment`
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().set_text("Text").without_default_attachment();
+    /// ```
+    pub fn without_default_attachment(mut self) -> Self {
+        self.skip_default_attachment = true;
+        self
+    }
+
+    /// Add a custom field to message, replacing the previous value of the key
+    ///
+    /// Custom fields must be configured on the rocket chat server.
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new()
+    ///     .set_text("Text")
+    ///     .set_custom_field("team", "payments")
+    ///     .set_custom_field("priority", 1);
+    /// ```
+    pub fn set_custom_field<K: Into<String>, V: Into<serde_json::Value>>(
+        mut self,
+        key: K,
+        value: V,
+    ) -> Self {
+        self.custom_fields.insert(key.into(), value.into());
+        self
+    }
+
+    /// Returns true if the body of message sent to a channel is at most `limit` bytes
+    ///
+    /// ```
+    /// if !message.fits("#channel", MAX_PAYLOAD_BYTES) {
+    ///     message = message.set_attachments(vec![]);
+    /// }
+    /// ```
+    pub fn fits(&self, channel: &str, limit: usize) -> bool {
+        let payload = RocketChatMessagePayload {
+            channel: Some(channel),
+            room_id: None,
+            message: self,
+        };
+        serde_json::to_vec(&payload).is_ok_and(|body| body.len() <= limit)
+    }
+
+    /// Render message as plain text, like for logs or an email fallback
+    ///
+    /// This is an approximation of how rocket chat displays the message: the
+    /// text is followed by the title, author, text, description, fields and
+    /// footer of each attachment, one block per attachment, with markdown
+    /// removed.
+    ///
+    /// ```
+    /// println!("{}", message.to_plaintext());
+    /// ```
+    pub fn to_plaintext(&self) -> String {
+        let mut blocks = Vec::new();
+        if let Some(text) = &self.text {
+            blocks.push(strip_markdown(text));
+        }
+        for attachment in &self.attachments {
+            let mut lines = Vec::new();
+            match (&attachment.title, &attachment.title_link) {
+                (Some(title), Some(link)) => {
+                    lines.push(format!("{} ({})", strip_markdown(title), link))
+                }
+                (Some(title), None) => lines.push(strip_markdown(title)),
+                _ => {}
+            }
+            if let Some(author) = &attachment.author_name {
+                lines.push(author.clone());
+            }
+            for text in [&attachment.text, &attachment.description]
+                .into_iter()
+                .flatten()
+            {
+                lines.push(strip_markdown(text));
+            }
+            for field in &attachment.fields {
+                lines.push(format!(
+                    "{}: {}",
+                    strip_markdown(&field.title),
+                    strip_markdown(&field.value)
+                ));
+            }
+            if let Some(footer) = &attachment.footer {
+                lines.push(strip_markdown(footer));
+            }
+            blocks.push(lines.join("\n"));
+        }
+        blocks.join("\n\n")
+    }
+
+    /// Check extra keys don't override keys managed by this crate
+    fn check_extras(&self) -> Result<(), Error> {
+        match self
+            .extras
+            .keys()
+            .find(|key| RESERVED_KEYS.contains(&key.as_str()))
+        {
+            Some(key) => bail!("Reserved extra key: {}", key),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true if the message has neither text nor attachments
+    fn is_empty(&self) -> bool {
+        self.text.as_deref().is_none_or(str::is_empty) && self.attachments.is_empty()
+    }
+
+    /// Check the message before sending
+    ///
+    /// A message must have a text or attachments, and each attachment must be
+    /// valid (see `RocketChatAttachment::validate`). An emoji and an avatar can't
+    /// be both set: rocket chat displays the emoji and ignores the avatar.
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new().set_text("Text");
+    ///
+    /// message.validate()?;
+    /// ```
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.is_empty() {
+            bail!("Empty message");
+        }
+        if self.emoji.is_some() && self.avatar.is_some() {
+            bail!("Both emoji and avatar are set, clear one of them");
+        }
+        self.check_extras()?;
+        for (i, attachment) in self.attachments.iter().enumerate() {
+            attachment
+                .validate()
+                .map_err(|e| anyhow!("Attachment {}: {}", i, e))?;
+        }
+        Ok(())
+    }
+
+    /// List the usages of the message ignored or unsupported by rocket chat,
+    /// without failing like `validate`
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new()
+    ///     .set_attachments(vec![RocketChatAttachment::new().set_title_link("https://google.fr")]);
+    ///
+    /// for lint in message.lint() {
+    ///     println!("{}", lint);
+    /// }
+    /// ```
+    pub fn lint(&self) -> Vec<Lint> {
+        let mut lints = Vec::new();
+        if self.is_empty() {
+            lints.push(Lint::message("empty message"));
+        }
+        if let Some(text) = &self.text {
+            let len = text.chars().count();
+            if len > MAX_MESSAGE_LENGTH {
+                lints.push(Lint::message(format!(
+                    "text of {} chars over the limit of {}",
+                    len, MAX_MESSAGE_LENGTH
+                )));
+            }
+        }
+        if self.emoji.is_some() && self.avatar.is_some() {
+            lints.push(Lint::message("avatar set with emoji, avatar is ignored"));
+        }
+        if let Err(e) = self.check_extras() {
+            lints.push(Lint::message(e.to_string()));
+        }
+        for (i, attachment) in self.attachments.iter().enumerate() {
+            attachment.lint_into(i, &mut lints);
+        }
+        lints
+    }
+
+    /// Check the colors of all attachments
+    ///
+    /// A color is valid if it is an hex color (`#rgb` or `#rrggbb`) or a color
+    /// name (like `good`, `warning`, `danger` or `red`). On error, the index of
+    /// the attachment and the color are returned for each invalid color.
+    ///
+    /// ```
+    /// let message = RocketChatMessage::new()
+    ///     .set_attachments(vec![RocketChatAttachment::new().set_color("#c97149")]);
+    ///
+    /// assert!(message.validate_colors().is_ok());
+    /// ```
+    pub fn validate_colors(&self) -> Result<(), Vec<(usize, String)>> {
+        let invalid: Vec<(usize, String)> = self
+            .attachments
+            .iter()
+            .enumerate()
+            .filter_map(|(i, attachment)| match &attachment.color {
+                Some(color) if !is_valid_color(color) => Some((i, color.clone())),
+                _ => None,
+            })
+            .collect();
+
+        if invalid.is_empty() {
+            Result::Ok(())
+        } else {
+            Err(invalid)
+        }
+    }
 }
@@ -0,0 +1,28 @@
+use anyhow::Error;
+
+/// A trait to record metrics of the messages sent by a client, see
+/// `RocketChat::set_metrics`
+///
+/// All methods do nothing by default, so only the needed ones are implemented.
+///
+/// ```
+/// struct Counters {
+///     failures: AtomicU64,
+/// }
+///
+/// impl Metrics for Counters {
+///     fn on_failure(&self, _error: &Error) {
+///         self.failures.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+/// ```
+pub trait Metrics: Send + Sync {
+    /// Called when a message is sent
+    fn on_success(&self) {}
+
+    /// Called when sending a message failed, after the retries
+    fn on_failure(&self, _error: &Error) {}
+
+    /// Called before each retry of a message
+    fn on_retry(&self) {}
+}
@@ -0,0 +1,97 @@
+use crate::{RocketChat, RocketChatMessage};
+use anyhow::*;
+use reqwest::blocking::Response;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Strategy used by a pool to pick the client sending a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolStrategy {
+    /// Each message is sent by the next client, in turn
+    RoundRobin,
+    /// Messages are sent by the first client, the next ones are tried on failure
+    Failover,
+}
+
+/// A structure representing a pool of rocket chat clients, for redundancy
+/// across multiple webhooks
+#[derive(Debug)]
+pub struct RocketChatPool {
+    /// Clients of the pool
+    clients: Vec<RocketChat>,
+    /// Strategy to pick the client
+    strategy: PoolStrategy,
+    /// Index of the next client for round robin
+    next: AtomicUsize,
+}
+
+impl RocketChatPool {
+    /// Creates a new pool of rocket chat clients
+    ///
+    /// ```
+    /// let pool = RocketChatPool::new(
+    ///     vec![
+    ///         RocketChat::new("ROCKET_CHAT_WEBHOOK_URL", "#channel"),
+    ///         RocketChat::new("ROCKET_CHAT_WEBHOOK_URL2", "#channel"),
+    ///     ],
+    ///     PoolStrategy::Failover,
+    /// );
+    /// ```
+    pub fn new(clients: Vec<RocketChat>, strategy: PoolStrategy) -> Self {
+        Self {
+            clients,
+            strategy,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the indexes of clients to try, in order
+    fn order(&self) -> Result<Vec<usize>, Error> {
+        if self.clients.is_empty() {
+            bail!("Empty pool");
+        }
+        Ok(match self.strategy {
+            PoolStrategy::RoundRobin => {
+                vec![self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len()]
+            }
+            PoolStrategy::Failover => (0..self.clients.len()).collect(),
+        })
+    }
+
+    /// Send a rocket chat message with a client of the pool
+    ///
+    /// With the failover strategy, the last error is returned if all clients failed.
+    ///
+    /// ```
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// pool.send_message(msg).await?;
+    /// ```
+    pub async fn send_message(&self, msg: RocketChatMessage) -> Result<reqwest::Response, Error> {
+        let mut error = None;
+        for i in self.order()? {
+            match self.clients[i].send_message(msg.clone()).await {
+                Result::Ok(res) => return Ok(res),
+                Err(e) => error = Some(e),
+            }
+        }
+        Err(error.unwrap_or_else(|| anyhow!("Empty pool")))
+    }
+
+    /// Send a rocket chat message with a client of the pool (sync)
+    ///
+    /// ```
+    /// let msg = RocketChatMessage::new().set_text("Text");
+    ///
+    /// pool.send_message_sync(msg);
+    /// ```
+    pub fn send_message_sync(&self, msg: RocketChatMessage) -> Result<Response, Error> {
+        let mut error = None;
+        for i in self.order()? {
+            match self.clients[i].send_message_sync(msg.clone()) {
+                Result::Ok(res) => return Ok(res),
+                Err(e) => error = Some(e),
+            }
+        }
+        Err(error.unwrap_or_else(|| anyhow!("Empty pool")))
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{escape_markdown, RocketChatMessage};
+use std::collections::HashMap;
+
+/// A structure representing a message template, with `{placeholder}` replaced
+/// by parameters when rendered
+///
+/// Substituted values are escaped with `escape_markdown`, so parameters can't
+/// inject markdown, but mentions in parameters still notify users. Use `{{`
+/// and `}}` for literal braces, and unknown placeholders are kept as is.
+#[derive(Debug, Clone)]
+pub struct MessageTemplate {
+    /// Template of the message text
+    text: String,
+}
+
+impl MessageTemplate {
+    /// Creates a new message template
+    ///
+    /// ```
+    /// let template = MessageTemplate::new("Deploy of *{service}* failed: {reason}");
+    /// ```
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        Self { text: text.into() }
+    }
+
+    /// Renders the text of the template with parameters
+    ///
+    /// ```
+    /// let mut params = HashMap::new();
+    /// params.insert("service".to_string(), "api".to_string());
+    ///
+    /// let text = template.render_text(&params);
+    /// ```
+    pub fn render_text(&self, params: &HashMap<String, String>) -> String {
+        let mut text = String::with_capacity(self.text.len());
+        let mut rest = self.text.as_str();
+
+        while let Some(i) = rest.find(['{', '}']) {
+            text.push_str(&rest[..i]);
+            rest = &rest[i..];
+
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                text.push_str(&rest[..1]);
+                rest = &rest[2..];
+                continue;
+            }
+            let value = rest
+                .strip_prefix('{')
+                .and_then(|placeholder| placeholder.split_once('}'))
+                .and_then(|(name, after)| Some((params.get(name)?, after)));
+            match value {
+                Some((value, after)) => {
+                    text.push_str(&escape_markdown(value));
+                    rest = after;
+                }
+                None => {
+                    text.push_str(&rest[..1]);
+                    rest = &rest[1..];
+                }
+            }
+        }
+        text.push_str(rest);
+        text
+    }
+
+    /// Renders the template as a message with parameters
+    ///
+    /// ```
+    /// let message = template.render(&params);
+    /// ```
+    pub fn render(&self, params: &HashMap<String, String>) -> RocketChatMessage {
+        RocketChatMessage::new().set_text(self.render_text(params))
+    }
+}
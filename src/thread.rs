@@ -0,0 +1,97 @@
+use crate::{RocketChat, RocketChatMessage};
+use anyhow::*;
+use reqwest::blocking::Response;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A structure representing a thread opened with `RocketChat::open_thread`,
+/// to post replies in it
+#[derive(Debug, Clone)]
+pub struct ThreadHandle {
+    /// Client posting the replies
+    client: RocketChat,
+    /// Id of the parent message of the thread
+    parent_message_id: String,
+}
+
+impl ThreadHandle {
+    /// Creates a handle on the thread of a parent message
+    pub(crate) fn new(client: RocketChat, parent_message_id: String) -> Self {
+        Self {
+            client,
+            parent_message_id,
+        }
+    }
+
+    /// Returns the id of the parent message of the thread
+    pub fn parent_message_id(&self) -> &str {
+        &self.parent_message_id
+    }
+
+    /// Send a rocket chat message as a reply in the thread
+    ///
+    /// ```
+    /// thread.reply(RocketChatMessage::new().set_text("Update")).await?;
+    /// ```
+    pub async fn reply(&self, msg: RocketChatMessage) -> Result<reqwest::Response, Error> {
+        self.client.reply_to(&self.parent_message_id, msg).await
+    }
+
+    /// Send a rocket chat message as a reply in the thread (sync)
+    ///
+    /// ```
+    /// thread.reply_sync(RocketChatMessage::new().set_text("Update"));
+    /// ```
+    pub fn reply_sync(&self, msg: RocketChatMessage) -> Result<Response, Error> {
+        self.client
+            .send_message_sync(msg.set_tmid(self.parent_message_id.as_str()))
+    }
+}
+
+/// A trait to store the parent message id of the thread of each key, see
+/// `RocketChat::reply_to_key`
+///
+/// ```
+/// struct RedisThreadStore {
+///     connection: Mutex<redis::Connection>,
+/// }
+///
+/// impl ThreadStore for RedisThreadStore {
+///     fn get(&self, key: &str) -> Option<String> {
+///         self.connection.lock().unwrap().get(key).ok()
+///     }
+///
+///     fn insert(&self, key: &str, parent_message_id: String) {
+///         let _: Result<(), _> = self.connection.lock().unwrap().set(key, parent_message_id);
+///     }
+/// }
+/// ```
+pub trait ThreadStore: Send + Sync {
+    /// Returns the parent message id of the thread of a key
+    fn get(&self, key: &str) -> Option<String>;
+
+    /// Stores the parent message id of the thread of a key
+    fn insert(&self, key: &str, parent_message_id: String);
+}
+
+/// A thread store in memory, the default of clients
+///
+/// Threads are lost when the process exits, so a later process opens new
+/// threads for the same keys.
+#[derive(Debug, Default)]
+pub struct MemoryThreadStore {
+    /// Parent message ids by key
+    threads: Mutex<HashMap<String, String>>,
+}
+
+impl ThreadStore for MemoryThreadStore {
+    fn get(&self, key: &str) -> Option<String> {
+        self.threads.lock().ok()?.get(key).cloned()
+    }
+
+    fn insert(&self, key: &str, parent_message_id: String) {
+        if let Result::Ok(mut threads) = self.threads.lock() {
+            threads.insert(key.to_string(), parent_message_id);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{BatchResult, RocketChat, RocketChatMessage};
+use anyhow::*;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+/// A job of the worker thread
+enum Job {
+    /// Send a message
+    Send(RocketChatMessage),
+    /// Notify once the messages queued before are sent
+    Flush(mpsc::Sender<()>),
+}
+
+// Messages are not Debug, so only the kind of job is shown
+impl std::fmt::Debug for Job {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Job::Send(_) => f.write_str("Send"),
+            Job::Flush(_) => f.write_str("Flush"),
+        }
+    }
+}
+
+/// A handle on a background thread sending messages, returned by
+/// `RocketChat::spawn_sync_worker`
+///
+/// Messages are sent in order with the blocking client. The handle must be
+/// joined with `join` before the program exits: if it is dropped, the thread
+/// keeps sending the queued messages but is killed with the process, so the
+/// messages not sent yet are silently lost.
+#[derive(Debug)]
+pub struct SyncSender {
+    /// Queue of jobs of the thread
+    sender: mpsc::Sender<Job>,
+    /// Thread sending the messages
+    worker: JoinHandle<BatchResult>,
+}
+
+impl SyncSender {
+    /// Spawns the thread sending messages with a client
+    pub(crate) fn spawn(client: RocketChat) -> Result<Self, Error> {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let worker = thread::Builder::new()
+            .name("rocketchat-worker".to_string())
+            .spawn(move || {
+                let mut result = BatchResult::default();
+                let mut index = 0;
+                for job in receiver {
+                    match job {
+                        Job::Send(msg) => {
+                            result.push(index, client.send_message_sync(msg));
+                            index += 1;
+                        }
+                        // The flusher may have given up waiting
+                        Job::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+                result
+            })
+            .map_err(|e| anyhow!("Failed to spawn worker thread: {}", e))?;
+
+        Ok(Self { sender, worker })
+    }
+
+    /// Queue a rocket chat message, without waiting for it to be sent
+    ///
+    /// ```
+    /// worker.send(RocketChatMessage::new().set_text("Text"))?;
+    /// ```
+    pub fn send(&self, msg: RocketChatMessage) -> Result<(), Error> {
+        self.sender
+            .send(Job::Send(msg))
+            .map_err(|_| anyhow!("Worker thread stopped"))
+    }
+
+    /// Wait for the messages queued so far to be sent, without stopping the
+    /// thread
+    ///
+    /// The results of the sends are returned by `join`.
+    ///
+    /// ```
+    /// worker.send(RocketChatMessage::new().set_text("Starting"))?;
+    /// worker.flush()?;
+    /// ```
+    pub fn flush(&self) -> Result<(), Error> {
+        let (done, flushed) = mpsc::channel();
+        self.sender
+            .send(Job::Flush(done))
+            .map_err(|_| anyhow!("Worker thread stopped"))?;
+        flushed.recv().map_err(|_| anyhow!("Worker thread stopped"))
+    }
+
+    /// Wait for the queued messages to be sent, then stop the thread
+    ///
+    /// The result indexes messages in the order they were queued.
+    ///
+    /// ```
+    /// worker.join()?.into_result()?;
+    /// ```
+    pub fn join(self) -> Result<BatchResult, Error> {
+        // Closing the queue ends the loop of the thread once it is drained
+        drop(self.sender);
+        self.worker
+            .join()
+            .map_err(|_| anyhow!("Worker thread panicked"))
+    }
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{block_on, response, MockServer};
+use rocketchat_message::{RocketChat, RocketChatMessage};
+
+#[test]
+fn confirmation_poll_is_bounded_by_timeout() {
+    let server = MockServer::start(|request| {
+        if request.method == "GET" {
+            // Hangs past the confirmation timeout
+            thread::sleep(Duration::from_secs(5));
+        }
+        response(
+            "200 OK",
+            &[("Content-Type", "application/json")],
+            br#"{"success":true,"message":{"_id":"ID","rid":"RID"}}"#,
+        )
+    });
+    let client =
+        RocketChat::new("", "#channel").set_auth(server.url.as_str(), "USER_ID", "AUTH_TOKEN");
+
+    let start = Instant::now();
+    let error = block_on(client.send_confirmed(
+        RocketChatMessage::new().set_text("Text"),
+        Duration::from_millis(300),
+    ))
+    .unwrap_err();
+
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(error.to_string(), "Message ID not confirmed after 300ms");
+}
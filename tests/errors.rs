@@ -0,0 +1,41 @@
+mod common;
+
+use common::{block_on, response, MockServer};
+use rocketchat_message::RocketChat;
+
+/// Starts a server answering a 400 with a body which is not UTF-8
+fn invalid_utf8_server() -> MockServer {
+    MockServer::start(|_| response("400 Bad Request", &[], b"bad \xff\xfe gateway"))
+}
+
+#[test]
+fn error_decodes_invalid_utf8_body() {
+    let server = invalid_utf8_server();
+    let client = RocketChat::new(
+        format!("{}/hooks/TOKEN", server.url),
+        "#channel".to_string(),
+    );
+
+    let error = block_on(client.send_text("Text")).unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Response error: 400 Bad Request: bad \u{fffd}\u{fffd} gateway"
+    );
+}
+
+#[test]
+fn error_decodes_invalid_utf8_body_sync() {
+    let server = invalid_utf8_server();
+    let client = RocketChat::new(
+        format!("{}/hooks/TOKEN", server.url),
+        "#channel".to_string(),
+    );
+
+    let error = client.send_text_sync("Text").unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Response error: 400 Bad Request: bad \u{fffd}\u{fffd} gateway"
+    );
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::{block_on, response, MockServer};
+use reqwest::redirect::Policy;
+use rocketchat_message::RocketChat;
+
+/// Starts a server redirecting the old webhook to the new one with a 307
+fn redirecting_server() -> MockServer {
+    MockServer::start(|request| match request.path.as_str() {
+        "/hooks/old" => response("307 Temporary Redirect", &[("Location", "/hooks/new")], b""),
+        "/hooks/new" => response("200 OK", &[], b"{\"success\":true}"),
+        _ => response("404 Not Found", &[], b""),
+    })
+}
+
+#[test]
+fn follows_307_with_default_policy() {
+    let server = redirecting_server();
+    let client = RocketChat::new(format!("{}/hooks/old", server.url), "#channel".to_string());
+
+    block_on(client.send_text("Text")).unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].method, "POST");
+    assert_eq!(requests[1].path, "/hooks/new");
+    // A 307 keeps the method and the body
+    assert_eq!(requests[1].body, requests[0].body);
+}
+
+#[test]
+fn follows_307_with_custom_policy() {
+    let server = redirecting_server();
+    let client = RocketChat::new(format!("{}/hooks/old", server.url), "#channel".to_string())
+        .set_redirect_policy(Policy::limited(2));
+
+    client.send_text_sync("Text").unwrap();
+
+    let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+    assert_eq!(paths, ["/hooks/old", "/hooks/new"]);
+}
+
+#[test]
+fn does_not_follow_307_with_none_policy() {
+    let server = redirecting_server();
+    let client = RocketChat::new(format!("{}/hooks/old", server.url), "#channel".to_string())
+        .set_redirect_policy(Policy::none());
+
+    let error = block_on(client.send_text("Text")).unwrap_err();
+
+    assert!(error.to_string().contains("307"), "{}", error);
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn keeps_http_client_set_before_builder_options() {
+    let server = redirecting_server();
+    let http_client = reqwest::Client::builder()
+        .redirect(Policy::none())
+        .build()
+        .unwrap();
+    let client = RocketChat::new(format!("{}/hooks/old", server.url), "#channel".to_string())
+        .set_http_client(http_client)
+        .set_connect_timeout(std::time::Duration::from_secs(2));
+
+    // The redirect is not followed, so the custom client was not replaced
+    assert!(block_on(client.send_text("Text")).is_err());
+    assert_eq!(server.requests().len(), 1);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{response, MockServer};
+use rocketchat_message::{RocketChat, RocketChatAttachment, RocketChatMessage};
+
+/// Sends a message to a mock webhook with a client built from its url, and
+/// returns the body received
+fn sent_body<F>(client: F, msg: RocketChatMessage) -> String
+where
+    F: FnOnce(String) -> RocketChat,
+{
+    let server = MockServer::start(|_| response("200 OK", &[], b"{\"success\":true}"));
+    let client = client(format!("{}/hooks/TOKEN", server.url));
+
+    client.send_message_sync(msg).unwrap();
+    String::from_utf8(server.requests().remove(0).body).unwrap()
+}
+
+#[test]
+fn attachment_only_message_has_no_text_key() {
+    let msg = RocketChatMessage::new()
+        .set_attachments(vec![RocketChatAttachment::new().set_title("Title")]);
+
+    let value = serde_json::to_value(&msg).unwrap();
+    assert!(value.get("text").is_none(), "{}", value);
+
+    let body = sent_body(|url| RocketChat::new(url, "#channel".to_string()), msg);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(body.get("text").is_none(), "{}", body);
+    assert_eq!(body["attachments"][0]["title"], "Title");
+}
+
+#[test]
+fn body_keys_are_in_declaration_order() {
+    let msg = RocketChatMessage::new()
+        .set_text("Text")
+        .set_attachments(vec![RocketChatAttachment::new()
+            .set_title("Title")
+            .set_color("#2eb886")
+            .set_fields(vec![("CPU", "93%", true).into()])
+            .set_extra("ts", serde_json::json!("2024-01-01T00:00:00Z"))])
+        .set_tmid("PARENT_ID")
+        .set_custom_field("team", "ops")
+        .set_custom_field("env", "prod")
+        .set_extra("zeta", serde_json::json!(1))
+        .set_extra("alpha", serde_json::json!(true));
+
+    let body = sent_body(|url| RocketChat::new(url, "#channel".to_string()), msg);
+
+    // Keys of custom fields and extras are sorted, after the modeled keys
+    assert_eq!(
+        body,
+        concat!(
+            r##"{"channel":"#channel","text":"Text","attachments":[{"title":"Title","##,
+            r##""title_link":null,"color":"#2eb886","author_name":null,"author_icon":null,"##,
+            r##""text":null,"image_url":null,"fields":[{"short":true,"title":"CPU","value":"93%"}],"##,
+            r##""ts":"2024-01-01T00:00:00Z"}],"tmid":"PARENT_ID","##,
+            r##""customFields":{"env":"prod","team":"ops"},"alpha":true,"zeta":1}"##,
+        )
+    );
+}
+
+#[test]
+fn room_id_comes_first() {
+    let msg = RocketChatMessage::new().set_text("Text");
+
+    let body = sent_body(
+        |url| RocketChat::new_default_channel(url).set_room_id("ROOM_ID"),
+        msg,
+    );
+
+    assert_eq!(
+        body,
+        r#"{"roomId":"ROOM_ID","text":"Text","attachments":[]}"#
+    );
+}
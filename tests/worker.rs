@@ -0,0 +1,31 @@
+mod common;
+
+use common::{response, MockServer};
+use rocketchat_message::{RocketChat, RocketChatMessage};
+
+#[test]
+fn flush_waits_for_queued_messages() {
+    let server = MockServer::start(|_| response("200 OK", &[], b"{\"success\":true}"));
+    let worker = RocketChat::new(
+        format!("{}/hooks/TOKEN", server.url),
+        "#channel".to_string(),
+    )
+    .spawn_sync_worker()
+    .unwrap();
+
+    for i in 0..3 {
+        worker
+            .send(RocketChatMessage::new().set_text(format!("Message {}", i)))
+            .unwrap();
+    }
+    worker.flush().unwrap();
+    assert_eq!(server.requests().len(), 3);
+
+    // The worker still sends after a flush
+    worker
+        .send(RocketChatMessage::new().set_text("Message 3"))
+        .unwrap();
+    let result = worker.join().unwrap();
+    assert_eq!((result.sent, result.failed), (4, 0));
+    assert_eq!(server.requests().len(), 4);
+}